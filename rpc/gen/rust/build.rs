@@ -15,7 +15,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .protoc_arg("--experimental_allow_proto3_optional")
         .file_descriptor_set_path(out_dir.join("tenant_service_descriptor.bin"))
         .compile(
-            &[format!("{}/tenant/v1/tenant_service.proto", PROTO_ROOT_DIR)],
+            &[
+                format!("{}/tenant/v1/tenant_service.proto", PROTO_ROOT_DIR),
+                format!("{}/tenant/v1/tenant_event.proto", PROTO_ROOT_DIR),
+            ],
+            &[PROTO_ROOT_DIR],
+        )?;
+    tonic_build::configure()
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .file_descriptor_set_path(out_dir.join("item_service_descriptor.bin"))
+        .compile(
+            &[format!("{}/item/v1/item_service.proto", PROTO_ROOT_DIR)],
             &[PROTO_ROOT_DIR],
         )?;
     Ok(())
@@ -13,3 +13,11 @@ pub mod tenant {
             tonic::include_file_descriptor_set!("tenant_service_descriptor");
     }
 }
+
+pub mod item {
+    pub mod v1 {
+        tonic::include_proto!("item.v1");
+        pub const ITEM_SERVICE_FILE_DESCRIPTOR_SET: &[u8] =
+            tonic::include_file_descriptor_set!("item_service_descriptor");
+    }
+}
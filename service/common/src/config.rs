@@ -0,0 +1,4 @@
+pub mod reload;
+pub mod source;
+
+pub use source::ConfigError;
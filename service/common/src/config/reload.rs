@@ -0,0 +1,106 @@
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// NOTE: 再起動せずに変更できる設定
+pub trait Reloadable: Clone + Send + Sync + 'static {
+    // NOTE: 再読み込みの対象になるキー
+    const KEYS: &'static [&'static str];
+
+    // NOTE: 検証に失敗した値は sources にエラーとして記録する
+    fn load(sources: &mut super::source::Sources) -> Self;
+
+    // NOTE: 変更された設定を `key: 変更前 -> 変更後` の形で返す
+    fn diff(&self, other: &Self) -> Vec<String>;
+}
+
+// NOTE: 設定ファイルの更新時刻をポーリングし、変わったら設定を読み直す
+// 再読み込みできるのは --config で指定した設定ファイルだけで、
+// 環境変数と CLI の値はファイルより優先されるため、それらで指定した設定はファイルを変えても変わらない
+// どちらの場合も起動時に警告を出す
+pub fn spawn<R: Reloadable>(
+    keys: &'static [super::source::Key],
+    args: &super::source::Args,
+    current: R,
+) -> tokio::sync::watch::Receiver<R> {
+    let (tx, rx) = tokio::sync::watch::channel(current);
+    let Some(path) = args.config_file.clone() else {
+        tracing::warn!(
+            "no config file is given with --config, so {} cannot be changed without a restart",
+            R::KEYS.join(", ")
+        );
+        return rx;
+    };
+    let sources = super::source::Sources::load(keys, args);
+    for name in R::KEYS {
+        if let Some(origin) = sources.overridden(name) {
+            tracing::warn!(
+                "{} is set by {}, so changing it in {} has no effect",
+                name,
+                origin,
+                path.display()
+            );
+        }
+    }
+    tokio::spawn(run(keys, path, args.clone(), tx));
+    rx
+}
+
+async fn run<R: Reloadable>(
+    keys: &'static [super::source::Key],
+    path: std::path::PathBuf,
+    args: super::source::Args,
+    tx: tokio::sync::watch::Sender<R>,
+) {
+    let mut modified = modified_at(&path);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if tx.is_closed() {
+            return;
+        }
+        let latest = modified_at(&path);
+        if latest == modified {
+            continue;
+        }
+        modified = latest;
+        reload(keys, &args, &tx);
+    }
+}
+
+fn modified_at(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// NOTE: 検証に失敗した場合は以前の設定のまま動かし続ける
+fn reload<R: Reloadable>(
+    keys: &'static [super::source::Key],
+    args: &super::source::Args,
+    tx: &tokio::sync::watch::Sender<R>,
+) {
+    let span = tracing::info_span!(
+        "config.reload",
+        config.file = ?args.config_file,
+        config.changes = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+        error.message = tracing::field::Empty,
+    );
+    let _enter = span.enter();
+
+    let mut sources = super::source::Sources::load(keys, args);
+    let runtime = R::load(&mut sources);
+    if let Err(e) = sources.finish() {
+        span.record("otel.status_code", "ERROR");
+        span.record("error.message", e.to_string());
+        tracing::warn!("keeping the previous config: {}", e);
+        return;
+    }
+
+    let changes = tx.borrow().diff(&runtime);
+    if changes.is_empty() {
+        tracing::info!("config file changed, but no reloadable setting changed");
+        return;
+    }
+    let changes = changes.join(", ");
+    span.record("config.changes", changes.as_str());
+    tracing::info!("applying config changes: {}", changes);
+    tx.send_replace(runtime);
+}
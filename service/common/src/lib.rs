@@ -0,0 +1,3 @@
+// NOTE: item-service と tenant-service で共通の設定の読み込みと observability の実装
+pub mod config;
+pub mod observe;
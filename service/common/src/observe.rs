@@ -0,0 +1,467 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig as _;
+use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+
+pub mod baggage;
+pub mod body;
+pub mod grpc_server;
+mod propagation;
+mod resource;
+mod sampler;
+pub mod span_policy;
+mod span_stats;
+
+// TODO: 環境変数から log level を取得する
+pub const LOG_LEVEL: tracing::Level = tracing::Level::INFO;
+
+type FilterHandle =
+    tracing_subscriber::reload::Handle<tracing_subscriber::EnvFilter, tracing_subscriber::Registry>;
+
+// NOTE: resource の既定の service.name と tracer の名前には呼び出し元の crate の情報を使う
+#[derive(Debug, Clone, Copy)]
+pub struct Service {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+// NOTE: 各サービスの設定の KEYS に otel.* を宣言しておく
+pub struct OpenTelemetry {
+    pub schema_url: String,
+    pub endpoint: String,
+    pub service_name: Option<String>,
+    pub deployment_environment: Option<String>,
+    pub propagators: Vec<propagation::Propagator>,
+    pub span_policy: span_policy::SpanPolicy,
+    pub shutdown_timeout: std::time::Duration,
+}
+
+impl OpenTelemetry {
+    pub fn load(sources: &mut crate::config::source::Sources) -> Self {
+        Self {
+            schema_url: sources.required("otel.schema_url"),
+            endpoint: sources.required("otel.endpoint"),
+            service_name: sources.optional("otel.service_name"),
+            deployment_environment: sources.optional("otel.deployment_environment"),
+            propagators: propagation::Propagator::load(sources, "otel.propagators"),
+            span_policy: span_policy::SpanPolicy::load(
+                sources,
+                "otel.span_policy.ignore",
+                "otel.span_policy.downgrade",
+            ),
+            shutdown_timeout: sources.seconds("otel.shutdown_timeout_seconds"),
+        }
+    }
+}
+
+// NOTE: 再起動せずに変更できる observability の設定 (log.filter と otel.sampling_ratio)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Runtime {
+    pub log_filter: String,
+    pub sampling_ratio: f64,
+}
+
+impl crate::config::reload::Reloadable for Runtime {
+    const KEYS: &'static [&'static str] = &["log.filter", "otel.sampling_ratio"];
+
+    fn load(sources: &mut crate::config::source::Sources) -> Self {
+        let runtime = Self {
+            log_filter: sources.required("log.filter"),
+            sampling_ratio: sources.required("otel.sampling_ratio"),
+        };
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&runtime.log_filter) {
+            sources.invalid("log.filter", e.to_string());
+        }
+        if !(0.0..=1.0).contains(&runtime.sampling_ratio) {
+            sources.invalid(
+                "otel.sampling_ratio",
+                "must be between 0.0 and 1.0".to_string(),
+            );
+        }
+        runtime
+    }
+
+    fn diff(&self, other: &Self) -> Vec<String> {
+        let mut changes = vec![];
+        if self.log_filter != other.log_filter {
+            changes.push(format!(
+                "log.filter: {} -> {}",
+                self.log_filter, other.log_filter
+            ));
+        }
+        if self.sampling_ratio != other.sampling_ratio {
+            changes.push(format!(
+                "otel.sampling_ratio: {} -> {}",
+                self.sampling_ratio, other.sampling_ratio
+            ));
+        }
+        changes
+    }
+}
+
+impl AsRef<Runtime> for Runtime {
+    fn as_ref(&self) -> &Runtime {
+        self
+    }
+}
+
+pub fn init(
+    service: Service,
+    config: &OpenTelemetry,
+    runtime: &Runtime,
+) -> Result<Telemetry, Box<dyn std::error::Error>> {
+    opentelemetry::global::set_text_map_propagator(propagation::propagator(&config.propagators));
+    let resource = resource::resource(service, config);
+    let span_stats = std::sync::Arc::new(span_stats::SpanStats::default());
+    let sampler = sampler::ReloadableSampler::new(runtime.sampling_ratio);
+    let tracer_provider = init_tracer(
+        resource.clone(),
+        &config.endpoint,
+        span_stats.clone(),
+        sampler.clone(),
+    )?;
+    let tracer = tracer_provider.tracer(service.name);
+    let _ = opentelemetry::global::set_tracer_provider(tracer_provider.clone());
+    let metrics = init_metrics(resource, &config.endpoint)?;
+    let filter = init_subscriber(tracer, metrics.clone(), &runtime.log_filter)?;
+    Ok(Telemetry {
+        tracer_provider,
+        metrics,
+        span_stats,
+        shutdown_timeout: config.shutdown_timeout,
+        sampler,
+        filter,
+    })
+}
+
+pub struct Telemetry {
+    tracer_provider: opentelemetry::sdk::trace::TracerProvider,
+    metrics: opentelemetry::sdk::metrics::controllers::BasicController,
+    span_stats: std::sync::Arc<span_stats::SpanStats>,
+    shutdown_timeout: std::time::Duration,
+    sampler: sampler::ReloadableSampler,
+    filter: FilterHandle,
+}
+
+impl Telemetry {
+    // NOTE: 設定の再読み込みで変わった log filter と sampling ratio を反映する
+    pub fn apply_runtime_changes<R>(&self, mut runtime: tokio::sync::watch::Receiver<R>)
+    where
+        R: AsRef<Runtime> + Send + Sync + 'static,
+    {
+        let sampler = self.sampler.clone();
+        let filter = self.filter.clone();
+        tokio::spawn(async move {
+            while runtime.changed().await.is_ok() {
+                let runtime = runtime.borrow().as_ref().clone();
+                sampler.set_ratio(runtime.sampling_ratio);
+                // NOTE: filter は config の読み込み時に検証済み
+                match tracing_subscriber::EnvFilter::try_new(&runtime.log_filter) {
+                    Ok(env_filter) => {
+                        if let Err(e) = filter.reload(env_filter) {
+                            tracing::error!("failed to reload log filter: {}", e);
+                        }
+                    }
+                    Err(e) => tracing::error!("invalid log filter: {}", e),
+                }
+            }
+        });
+    }
+
+    // NOTE: flush と shutdown は export の完了を同期的に待つため、collector に繋がらない場合でも
+    // shutdown_timeout を超えて終了が遅れないよう、完了を待たずに戻れるようにする
+    pub async fn shutdown(self) {
+        let Self {
+            tracer_provider,
+            metrics,
+            span_stats,
+            shutdown_timeout,
+            ..
+        } = self;
+        let shutdown = run_with_deadline(shutdown_timeout, move || {
+            for result in tracer_provider.force_flush() {
+                if let Err(e) = result {
+                    tracing::error!("failed to flush spans: {}", e);
+                }
+            }
+            if let Err(e) = metrics.stop(&opentelemetry::Context::current()) {
+                tracing::error!("failed to stop metrics controller: {}", e);
+            }
+            opentelemetry::global::shutdown_tracer_provider();
+            // NOTE: ここで最後の参照が drop され、span processor が shutdown される
+            drop(tracer_provider);
+        });
+        if let Err(e) = shutdown.await {
+            tracing::warn!("failed to shutdown telemetry: {}", e);
+        }
+        span_stats.log_summary();
+    }
+}
+
+// NOTE: spawn_blocking の task は runtime の drop 時に完了を待たれるため、専用の thread で実行する
+// deadline を過ぎた thread は止められないが、待たずに戻ればプロセスの終了とともに破棄される
+async fn run_with_deadline(
+    deadline: std::time::Duration,
+    f: impl FnOnce() + Send + 'static,
+) -> Result<(), String> {
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+    std::thread::Builder::new()
+        .name("telemetry-shutdown".to_string())
+        .spawn(move || {
+            f();
+            let _ = done_tx.send(());
+        })
+        .map_err(|e| e.to_string())?;
+    match tokio::time::timeout(deadline, done_rx).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(_)) => Err("shutdown thread panicked".to_string()),
+        Err(_) => Err(format!("timed out after {:?}", deadline)),
+    }
+}
+
+fn init_subscriber(
+    tracer: opentelemetry::sdk::trace::Tracer,
+    metrics: opentelemetry::sdk::metrics::controllers::BasicController,
+    log_filter: &str,
+) -> Result<FilterHandle, Box<dyn std::error::Error>> {
+    // NOTE: 再読み込みできるよう filter は reload layer で包み、registry の直上に置く
+    let (filter, handle) =
+        tracing_subscriber::reload::Layer::new(tracing_subscriber::EnvFilter::try_new(log_filter)?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with(tracing_opentelemetry::MetricsLayer::new(metrics))
+        .try_init()?;
+    Ok(handle)
+}
+
+fn init_tracer(
+    resource: opentelemetry::sdk::Resource,
+    otel_endpoint: impl Into<String>,
+    span_stats: std::sync::Arc<span_stats::SpanStats>,
+    sampler: sampler::ReloadableSampler,
+) -> Result<opentelemetry::sdk::trace::TracerProvider, opentelemetry::trace::TraceError> {
+    let exporter = opentelemetry_otlp::SpanExporterBuilder::from(
+        opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(otel_endpoint),
+    )
+    .build_span_exporter()?;
+    // NOTE: baggage を attribute にコピーするため、install_batch を使わずに provider を組み立てる
+    Ok(opentelemetry::sdk::trace::TracerProvider::builder()
+        .with_span_processor(baggage::BaggageSpanProcessor::new([baggage::TENANT_ID_KEY]))
+        .with_span_processor(span_stats::CountingSpanProcessor(span_stats.clone()))
+        .with_batch_exporter(
+            span_stats::CountingSpanExporter::new(exporter, span_stats),
+            opentelemetry::sdk::runtime::Tokio,
+        )
+        .with_config(
+            opentelemetry::sdk::trace::config()
+                .with_id_generator(opentelemetry::sdk::trace::RandomIdGenerator::default())
+                .with_sampler(sampler)
+                .with_resource(resource),
+        )
+        .build())
+}
+
+// NOTE: metrics を送るには info を特定の形にする必要がある
+// read mores: https://blog.ymgyt.io/entry/starting_opentelemetry_with_rust/#prometheus
+fn init_metrics(
+    resource: opentelemetry::sdk::Resource,
+    otel_endpoint: impl Into<String>,
+) -> Result<
+    opentelemetry::sdk::metrics::controllers::BasicController,
+    opentelemetry::metrics::MetricsError,
+> {
+    opentelemetry_otlp::new_pipeline()
+        .metrics(
+            AggregatorSelector,
+            opentelemetry::sdk::export::metrics::aggregation::cumulative_temporality_selector(),
+            opentelemetry::sdk::runtime::Tokio,
+        )
+        .with_resource(resource)
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(otel_endpoint),
+        )
+        .build()
+}
+
+const DURATION_BOUNDARIES: [f64; 14] = [
+    5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 2500.0, 5000.0, 7500.0,
+    10000.0,
+];
+const SIZE_BOUNDARIES: [f64; 10] = [
+    64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+];
+
+// NOTE: inexpensive selector では histogram が sum として集計されるため bucket を持つ aggregator を選ぶ
+#[derive(Debug)]
+struct AggregatorSelector;
+
+impl opentelemetry::sdk::export::metrics::AggregatorSelector for AggregatorSelector {
+    fn aggregator_for(
+        &self,
+        descriptor: &opentelemetry::sdk::metrics::sdk_api::Descriptor,
+    ) -> Option<
+        std::sync::Arc<dyn opentelemetry::sdk::metrics::aggregators::Aggregator + Send + Sync>,
+    > {
+        use opentelemetry::sdk::metrics::{aggregators, sdk_api::InstrumentKind};
+
+        match descriptor.instrument_kind() {
+            InstrumentKind::GaugeObserver => Some(std::sync::Arc::new(aggregators::last_value())),
+            InstrumentKind::Histogram => match descriptor.unit() {
+                Some("By") => Some(std::sync::Arc::new(aggregators::histogram(
+                    &SIZE_BOUNDARIES,
+                ))),
+                _ => Some(std::sync::Arc::new(aggregators::histogram(
+                    &DURATION_BOUNDARIES,
+                ))),
+            },
+            _ => Some(std::sync::Arc::new(aggregators::sum())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{Tracer as _, TracerProvider as _};
+
+    const KEYS: &[crate::config::source::Key] = &[
+        crate::config::source::Key {
+            name: "otel.schema_url",
+            env: &[],
+            default: Some("https://opentelemetry.io/schemas/1.20.0"),
+        },
+        crate::config::source::Key {
+            name: "otel.endpoint",
+            env: &[],
+            default: Some("http://localhost:4317"),
+        },
+        crate::config::source::Key {
+            name: "otel.propagators",
+            env: &[],
+            default: Some("tracecontext,baggage"),
+        },
+        crate::config::source::Key {
+            name: "otel.span_policy.ignore",
+            env: &[],
+            default: None,
+        },
+        crate::config::source::Key {
+            name: "otel.span_policy.downgrade",
+            env: &[],
+            default: None,
+        },
+        crate::config::source::Key {
+            name: "otel.shutdown_timeout_seconds",
+            env: &[],
+            default: Some("5"),
+        },
+    ];
+
+    fn load(args: &[&str]) -> (super::OpenTelemetry, Result<(), String>) {
+        let args =
+            crate::config::source::Args::parse(args.iter().map(|arg| arg.to_string())).unwrap();
+        let mut sources = crate::config::source::Sources::load(KEYS, &args);
+        let config = super::OpenTelemetry::load(&mut sources);
+        (config, sources.finish().map_err(|e| e.to_string()))
+    }
+
+    #[test]
+    fn propagators_and_span_policy_are_parsed_while_loading() {
+        let (config, result) = load(&[
+            "--otel.propagators=b3multi,jaeger",
+            "--otel.span_policy.ignore=/grpc.health.v1.Health/*",
+            "--otel.span_policy.downgrade=regex:^/grpc\\.reflection\\.",
+        ]);
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(
+            config.propagators,
+            [
+                super::propagation::Propagator::B3Multi,
+                super::propagation::Propagator::Jaeger
+            ]
+        );
+        assert_eq!(
+            config.span_policy.action("/grpc.health.v1.Health/Check"),
+            super::span_policy::Action::Ignore
+        );
+        assert_eq!(
+            config
+                .span_policy
+                .action("/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo"),
+            super::span_policy::Action::Downgrade
+        );
+    }
+
+    #[test]
+    fn invalid_propagators_and_span_policy_are_collected_with_other_errors() {
+        let (_, result) = load(&[
+            "--otel.propagators=tracecontext,xray",
+            "--otel.span_policy.ignore=regex:(",
+            "--otel.span_policy.downgrade=regex:[",
+            "--otel.shutdown_timeout_seconds=soon",
+        ]);
+        let e = result.unwrap_err();
+        for expected in [
+            "invalid value for otel.propagators: unknown propagator: xray",
+            "invalid value for otel.span_policy.ignore: regex:(:",
+            "invalid value for otel.span_policy.downgrade: regex:[:",
+            "invalid value for otel.shutdown_timeout_seconds",
+        ] {
+            assert!(e.contains(expected), "{} not in {}", expected, e);
+        }
+    }
+
+    // NOTE: collector が応答しない状況を再現するため、export が完了しない exporter を使う
+    #[derive(Debug)]
+    struct HangingExporter;
+
+    impl opentelemetry::sdk::export::trace::SpanExporter for HangingExporter {
+        fn export(
+            &mut self,
+            _: Vec<opentelemetry::sdk::export::trace::SpanData>,
+        ) -> futures_core::future::BoxFuture<'static, opentelemetry::sdk::export::trace::ExportResult>
+        {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_gives_up_on_a_hanging_exporter_after_the_deadline() {
+        let tracer_provider = opentelemetry::sdk::trace::TracerProvider::builder()
+            .with_batch_exporter(HangingExporter, opentelemetry::sdk::runtime::Tokio)
+            .build();
+        tracer_provider.tracer("test").in_span("span", |_| {});
+
+        let start = std::time::Instant::now();
+        let result = super::run_with_deadline(std::time::Duration::from_millis(100), move || {
+            for _ in tracer_provider.force_flush() {}
+            drop(tracer_provider);
+        })
+        .await;
+        assert_eq!(result, Err("timed out after 100ms".to_string()));
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    // NOTE: runtime の drop が thread の完了を待つと、このテストは終わらない
+    #[tokio::test]
+    async fn run_with_deadline_does_not_hold_the_runtime() {
+        let result = super::run_with_deadline(std::time::Duration::from_millis(100), || loop {
+            std::thread::park();
+        })
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn run_with_deadline_waits_for_completion() {
+        let result = super::run_with_deadline(std::time::Duration::from_secs(5), || {
+            std::thread::sleep(std::time::Duration::from_millis(10))
+        })
+        .await;
+        assert_eq!(result, Ok(()));
+    }
+}
@@ -0,0 +1,54 @@
+use opentelemetry::baggage::BaggageExt as _;
+use opentelemetry::trace::Span as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+pub const TENANT_ID_KEY: &str = "tenant.id";
+
+// NOTE: baggage は span の親 Context から子 span に引き継がれるため、
+// 返り値の Context を親にした span の内側で下流を呼び出す
+pub fn with_tenant_id(tenant_id: impl ToString) -> opentelemetry::Context {
+    tracing::Span::current()
+        .context()
+        .with_baggage([opentelemetry::KeyValue::new(
+            TENANT_ID_KEY,
+            tenant_id.to_string(),
+        )])
+}
+
+// 親 Context の baggage のうち指定した key を span の attribute としてコピーする
+#[derive(Debug)]
+pub struct BaggageSpanProcessor {
+    keys: Vec<opentelemetry::Key>,
+}
+
+impl BaggageSpanProcessor {
+    pub fn new<I: IntoIterator<Item = &'static str>>(keys: I) -> Self {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(opentelemetry::Key::from_static_str)
+                .collect(),
+        }
+    }
+}
+
+impl opentelemetry::sdk::trace::SpanProcessor for BaggageSpanProcessor {
+    fn on_start(&self, span: &mut opentelemetry::sdk::trace::Span, cx: &opentelemetry::Context) {
+        let baggage = cx.baggage();
+        for key in &self.keys {
+            if let Some(value) = baggage.get(key.clone()) {
+                span.set_attribute(opentelemetry::KeyValue::new(key.clone(), value.clone()));
+            }
+        }
+    }
+
+    fn on_end(&self, _span: opentelemetry::sdk::export::trace::SpanData) {}
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+}
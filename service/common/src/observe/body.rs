@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// NOTE: リクエストボディから実際に読み出されたバイト数
+// ボディを読み終える前に drop された場合は、それまでに読んだ分だけになる
+#[derive(Debug, Clone, Default)]
+pub struct ReadBytes(Arc<AtomicU64>);
+
+impl ReadBytes {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// NOTE: size_hint はストリーミングや chunked では確定しないため、ボディを包んで読んだ分を数える
+// tonic と axum の内側のサービスは hyper::Body を受け取るため、Stream として包み直す
+pub fn count_read_bytes<B>(body: B) -> (tonic::transport::Body, ReadBytes)
+where
+    B: http_body::Body + Send + 'static,
+    B::Data: Into<bytes::Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let read_bytes = ReadBytes::default();
+    let body = CountingBody {
+        inner: body,
+        read_bytes: read_bytes.clone(),
+    };
+    (tonic::transport::Body::wrap_stream(body), read_bytes)
+}
+
+pin_project_lite::pin_project! {
+    struct CountingBody<B> {
+        #[pin]
+        inner: B,
+        read_bytes: ReadBytes,
+    }
+}
+
+impl<B> futures_core::Stream for CountingBody<B>
+where
+    B: http_body::Body,
+{
+    type Item = Result<B::Data, B::Error>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll_data(cx));
+        if let Some(Ok(chunk)) = &result {
+            this.read_bytes
+                .0
+                .fetch_add(bytes::Buf::remaining(chunk) as u64, Ordering::Relaxed);
+        }
+        std::task::Poll::Ready(result)
+    }
+}
@@ -0,0 +1,341 @@
+use opentelemetry::baggage::BaggageExt as _;
+use opentelemetry::trace::TraceContextExt as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use crate::observe::LOG_LEVEL;
+
+pub mod metrics;
+
+pub type TlsConnectInfo =
+    tonic::transport::server::TlsConnectInfo<tonic::transport::server::TcpConnectInfo>;
+
+pub type TraceLayer = tower::layer::util::Stack<
+    metrics::RpcServerMetricsLayer,
+    tower_http::trace::TraceLayer<
+        MakeClassify,
+        OpentelemetryMakeSpan,
+        OpentelemetryOnRequest,
+        OpentelemetryOnResponse,
+        tower_http::trace::DefaultOnBodyChunk,
+        OpentelemetryOnEos,
+        OpentelemetryOnFailure,
+    >,
+>;
+
+pub fn trace_layer(policy: super::span_policy::SpanPolicy) -> TraceLayer {
+    tower::layer::util::Stack::new(
+        metrics::RpcServerMetricsLayer::new(),
+        tower_http::trace::TraceLayer::new(MakeClassify)
+            .make_span_with(OpentelemetryMakeSpan::new(policy))
+            .on_request(OpentelemetryOnRequest)
+            .on_response(OpentelemetryOnResponse)
+            .on_eos(OpentelemetryOnEos)
+            .on_failure(OpentelemetryOnFailure),
+    )
+}
+
+#[derive(Copy, Clone)]
+pub struct MakeClassify;
+
+impl tower_http::classify::MakeClassifier for MakeClassify {
+    type Classifier = Classifier;
+    type FailureClass = FailureClass;
+    type ClassifyEos = ClassifyEos;
+
+    fn make_classifier<B>(&self, _: &http::Request<B>) -> Self::Classifier {
+        Classifier
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Classifier;
+
+impl tower_http::classify::ClassifyResponse for Classifier {
+    type FailureClass = FailureClass;
+    type ClassifyEos = ClassifyEos;
+
+    fn classify_response<B>(
+        self,
+        res: &http::Response<B>,
+    ) -> tower_http::classify::ClassifiedResponse<Self::FailureClass, Self::ClassifyEos> {
+        // NOTE: trailers-only のレスポンスは header に grpc-status が含まれる
+        // それ以外は trailers を受け取るまで status が決まらない
+        match tonic::Status::from_header_map(res.headers()) {
+            Some(status) => {
+                tower_http::classify::ClassifiedResponse::Ready(classify_status(status))
+            }
+            None => tower_http::classify::ClassifiedResponse::RequiresEos(ClassifyEos),
+        }
+    }
+
+    fn classify_error<E>(self, error: &E) -> Self::FailureClass
+    where
+        E: std::fmt::Display + 'static,
+    {
+        Self::FailureClass {
+            code: tonic::Code::Unknown,
+            message: error.to_string(),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct ClassifyEos;
+
+impl tower_http::classify::ClassifyEos for ClassifyEos {
+    type FailureClass = FailureClass;
+
+    fn classify_eos(self, trailers: Option<&http::HeaderMap>) -> Result<(), Self::FailureClass> {
+        match trailers.and_then(tonic::Status::from_header_map) {
+            Some(status) => classify_status(status),
+            None => Err(FailureClass {
+                code: tonic::Code::Unknown,
+                message: "missing grpc-status in trailers".to_string(),
+            }),
+        }
+    }
+
+    fn classify_error<E>(self, error: &E) -> Self::FailureClass
+    where
+        E: std::fmt::Display + 'static,
+    {
+        Self::FailureClass {
+            code: tonic::Code::Unknown,
+            message: error.to_string(),
+        }
+    }
+}
+
+fn classify_status(status: tonic::Status) -> Result<(), FailureClass> {
+    match status.code() {
+        tonic::Code::Ok => Ok(()),
+        code => Err(FailureClass {
+            code,
+            message: status.message().to_string(),
+        }),
+    }
+}
+
+pub trait FailureClassExt {
+    fn code(&self) -> tonic::Code;
+    fn message(&self) -> String;
+}
+
+pub struct FailureClass {
+    code: tonic::Code,
+    message: String,
+}
+
+impl FailureClassExt for FailureClass {
+    fn code(&self) -> tonic::Code {
+        self.code
+    }
+
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct OpentelemetryMakeSpan {
+    policy: std::sync::Arc<super::span_policy::SpanPolicy>,
+}
+
+impl OpentelemetryMakeSpan {
+    pub fn new(policy: super::span_policy::SpanPolicy) -> Self {
+        Self {
+            policy: std::sync::Arc::new(policy),
+        }
+    }
+}
+
+impl<B> tower_http::trace::MakeSpan<B> for OpentelemetryMakeSpan {
+    fn make_span(&mut self, req: &http::Request<B>) -> tracing::Span {
+        let span = match self.policy.action(req.uri().path()) {
+            super::span_policy::Action::Ignore => return tracing::Span::none(),
+            super::span_policy::Action::Downgrade => tracing::debug_span!(
+                "",
+                otel.name = %req.uri().path(),
+                otel.kind = "server",
+                rpc.system = "grpc",
+                rpc.method = tracing::field::Empty,
+                rpc.service = tracing::field::Empty,
+                rpc.grpc.full_method = tracing::field::Empty,
+                rpc.grpc.status_code = tracing::field::Empty,
+                rpc.grpc.message = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty,
+                error.message = tracing::field::Empty,
+                net.peer.ip = tracing::field::Empty,
+                net.peer.port = tracing::field::Empty,
+                net.host.port = tracing::field::Empty,
+                tls.client.subject = tracing::field::Empty,
+                user_agent.original = tracing::field::Empty,
+            ),
+            super::span_policy::Action::Record => tracing::span!(
+                LOG_LEVEL,
+                "",
+                otel.name = %req.uri().path(),
+                otel.kind = "server",
+                rpc.system = "grpc",
+                rpc.method = tracing::field::Empty,
+                rpc.service = tracing::field::Empty,
+                rpc.grpc.full_method = tracing::field::Empty,
+                rpc.grpc.status_code = tracing::field::Empty,
+                rpc.grpc.message = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty,
+                error.message = tracing::field::Empty,
+                net.peer.ip = tracing::field::Empty,
+                net.peer.port = tracing::field::Empty,
+                net.host.port = tracing::field::Empty,
+                tls.client.subject = tracing::field::Empty,
+                user_agent.original = tracing::field::Empty,
+            ),
+        };
+
+        let parent_cx = opentelemetry::global::get_text_map_propagator(|p| {
+            p.extract(&opentelemetry_http::HeaderExtractor(req.headers()))
+        });
+        // NOTE: trace context がなくても baggage だけ伝播される場合がある
+        if parent_cx.span().span_context().is_valid() || !parent_cx.baggage().is_empty() {
+            span.set_parent(parent_cx);
+        }
+
+        span
+    }
+}
+
+#[derive(Clone)]
+pub struct OpentelemetryOnRequest;
+
+impl<B> tower_http::trace::OnRequest<B> for OpentelemetryOnRequest {
+    fn on_request(&mut self, req: &http::Request<B>, span: &tracing::Span) {
+        let full_method = req.uri().path();
+
+        span.record("rpc.grpc.full_method", full_method);
+
+        let methods: Vec<_> = full_method.split("/").collect();
+        if let (Some(service), Some(method)) = (methods.get(1), methods.get(2)) {
+            span.record(
+                opentelemetry_semantic_conventions::trace::RPC_SERVICE.as_str(),
+                service,
+            );
+            span.record(
+                opentelemetry_semantic_conventions::trace::RPC_METHOD.as_str(),
+                method,
+            );
+        }
+
+        if let Some(info) = tcp_connect_info(req) {
+            if let Some(remote) = info.remote_addr() {
+                span.record("net.peer.ip", tracing::field::display(remote.ip()));
+                span.record("net.peer.port", remote.port());
+            }
+            if let Some(local) = info.local_addr() {
+                span.record("net.host.port", local.port());
+            }
+        }
+        if let Some(subject) = peer_subject(req) {
+            span.record("tls.client.subject", subject.as_str());
+        }
+        if let Some(user_agent) = req
+            .headers()
+            .get(http::header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+        {
+            span.record("user_agent.original", user_agent);
+        }
+    }
+}
+
+// NOTE: TLS で待ち受けている場合は TlsConnectInfo の内側に TcpConnectInfo が入る
+fn tcp_connect_info<B>(
+    req: &http::Request<B>,
+) -> Option<&tonic::transport::server::TcpConnectInfo> {
+    req.extensions()
+        .get::<tonic::transport::server::TcpConnectInfo>()
+        .or_else(|| {
+            req.extensions()
+                .get::<TlsConnectInfo>()
+                .map(|info| info.get_ref())
+        })
+}
+
+// NOTE: クライアント証明書の subject を返す (mTLS でない場合や TLS でない場合は None)
+fn peer_subject<B>(req: &http::Request<B>) -> Option<String> {
+    let info = req.extensions().get::<TlsConnectInfo>()?;
+    let certs = info.peer_certs()?;
+    let (_, cert) = x509_parser::parse_x509_certificate(certs.first()?.get_ref()).ok()?;
+    Some(cert.subject().to_string())
+}
+
+#[derive(Clone)]
+pub struct OpentelemetryOnResponse;
+
+impl<B> tower_http::trace::OnResponse<B> for OpentelemetryOnResponse {
+    fn on_response(
+        self,
+        res: &http::Response<B>,
+        _latency: std::time::Duration,
+        span: &tracing::Span,
+    ) {
+        // NOTE: trailers-only のレスポンス以外は OnEos で記録する
+        if let Some(status) = tonic::Status::from_header_map(res.headers()) {
+            record_status(span, &status);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OpentelemetryOnEos;
+
+impl tower_http::trace::OnEos for OpentelemetryOnEos {
+    fn on_eos(
+        self,
+        trailers: Option<&http::HeaderMap>,
+        _stream_duration: std::time::Duration,
+        span: &tracing::Span,
+    ) {
+        if let Some(status) = trailers.and_then(tonic::Status::from_header_map) {
+            record_status(span, &status);
+        }
+    }
+}
+
+fn record_status(span: &tracing::Span, status: &tonic::Status) {
+    span.record(
+        opentelemetry_semantic_conventions::trace::RPC_GRPC_STATUS_CODE.as_str(),
+        status.code() as i32,
+    );
+    if !status.message().is_empty() {
+        span.record("rpc.grpc.message", status.message());
+    }
+    let status_code = match status.code() {
+        tonic::Code::Ok => "OK",
+        _ => "ERROR",
+    };
+    span.record("otel.status_code", status_code);
+}
+
+#[derive(Clone)]
+pub struct OpentelemetryOnFailure;
+
+impl<F> tower_http::trace::OnFailure<F> for OpentelemetryOnFailure
+where
+    F: FailureClassExt,
+{
+    fn on_failure(
+        &mut self,
+        failure_classification: F,
+        _latency: std::time::Duration,
+        span: &tracing::Span,
+    ) {
+        tracing::error!("{}", failure_classification.message());
+        span.record(
+            opentelemetry_semantic_conventions::trace::RPC_GRPC_STATUS_CODE.as_str(),
+            failure_classification.code() as i32,
+        );
+        span.record("rpc.grpc.message", failure_classification.message());
+        span.record("otel.status_code", "ERROR");
+        span.record("error.message", failure_classification.message());
+    }
+}
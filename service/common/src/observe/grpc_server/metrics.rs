@@ -0,0 +1,286 @@
+use opentelemetry::metrics::{Histogram, Unit, UpDownCounter};
+
+// read more: https://opentelemetry.io/docs/specs/otel/metrics/semantic_conventions/rpc-metrics/
+#[derive(Clone)]
+pub struct RpcServerMetrics {
+    duration: Histogram<f64>,
+    request_size: Histogram<u64>,
+    response_size: Histogram<u64>,
+    active_requests: UpDownCounter<i64>,
+}
+
+impl RpcServerMetrics {
+    pub fn new() -> Self {
+        let meter = opentelemetry::global::meter(env!("CARGO_PKG_NAME"));
+        Self {
+            duration: meter
+                .f64_histogram("rpc.server.duration")
+                .with_description("Measures the duration of inbound RPC.")
+                .with_unit(Unit::new("ms"))
+                .init(),
+            request_size: meter
+                .u64_histogram("rpc.server.request.size")
+                .with_description("Measures the size of RPC request messages.")
+                .with_unit(Unit::new("By"))
+                .init(),
+            response_size: meter
+                .u64_histogram("rpc.server.response.size")
+                .with_description("Measures the size of RPC response messages.")
+                .with_unit(Unit::new("By"))
+                .init(),
+            active_requests: meter
+                .i64_up_down_counter("rpc.server.active_requests")
+                .with_description(
+                    "Measures the number of concurrent RPCs that are currently in-flight.",
+                )
+                .init(),
+        }
+    }
+}
+
+impl Default for RpcServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
+pub struct RpcServerMetricsLayer {
+    metrics: RpcServerMetrics,
+}
+
+impl RpcServerMetricsLayer {
+    pub fn new() -> Self {
+        Self {
+            metrics: RpcServerMetrics::new(),
+        }
+    }
+}
+
+impl Default for RpcServerMetricsLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> tower::Layer<S> for RpcServerMetricsLayer {
+    type Service = RpcServerMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpcServerMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RpcServerMetricsService<S> {
+    inner: S,
+    metrics: RpcServerMetrics,
+}
+
+impl<S, ReqBody, ResBody> tower::Service<http::Request<ReqBody>> for RpcServerMetricsService<S>
+where
+    S: tower::Service<http::Request<tonic::transport::Body>, Response = http::Response<ResBody>>,
+    ReqBody: http_body::Body + Send + 'static,
+    ReqBody::Data: Into<bytes::Bytes>,
+    ReqBody::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResBody: http_body::Body,
+{
+    type Response = http::Response<ResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let (parts, body) = req.into_parts();
+        let (body, request_size) = crate::observe::body::count_read_bytes(body);
+        let req = http::Request::from_parts(parts, body);
+        let call = Call::start(self.metrics.clone(), &req, request_size);
+        ResponseFuture {
+            inner: self.inner.call(req),
+            call: Some(call),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        call: Option<Call>,
+    }
+}
+
+impl<F, ResBody, E> std::future::Future for ResponseFuture<F>
+where
+    F: std::future::Future<Output = Result<http::Response<ResBody>, E>>,
+{
+    type Output = Result<http::Response<ResponseBody<ResBody>>, E>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll(cx));
+        let mut call = this.call.take().expect("polled after completion");
+        match result {
+            Ok(res) => {
+                // NOTE: エラー時は trailers-only で返されるため header に grpc-status が含まれる
+                if let Some(status) = tonic::Status::from_header_map(res.headers()) {
+                    call.code = Some(status.code());
+                }
+                std::task::Poll::Ready(Ok(res.map(|inner| ResponseBody {
+                    inner,
+                    call: Some(call),
+                })))
+            }
+            Err(e) => {
+                call.code = Some(tonic::Code::Unknown);
+                std::task::Poll::Ready(Err(e))
+            }
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseBody<B> {
+        #[pin]
+        inner: B,
+        call: Option<Call>,
+    }
+}
+
+impl<B> http_body::Body for ResponseBody<B>
+where
+    B: http_body::Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll_data(cx));
+        if let Some(call) = this.call.as_mut() {
+            match &result {
+                Some(Ok(chunk)) => call.response_size += bytes::Buf::remaining(chunk) as u64,
+                Some(Err(_)) => call.code = Some(tonic::Code::Unknown),
+                None => {}
+            }
+        }
+        std::task::Poll::Ready(result)
+    }
+
+    fn poll_trailers(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll_trailers(cx));
+        if let Some(mut call) = this.call.take() {
+            match &result {
+                Ok(Some(trailers)) => {
+                    if let Some(status) = tonic::Status::from_header_map(trailers) {
+                        call.code = Some(status.code());
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => call.code = Some(tonic::Code::Unknown),
+            }
+        }
+        std::task::Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// NOTE: Drop 時に計測値を記録する
+// ストリームの途中でクライアントが切断した場合も in-flight 数が戻るようにするため
+struct Call {
+    metrics: RpcServerMetrics,
+    attributes: Vec<opentelemetry::KeyValue>,
+    start: std::time::Instant,
+    code: Option<tonic::Code>,
+    request_size: crate::observe::body::ReadBytes,
+    response_size: u64,
+}
+
+impl Call {
+    fn start<B>(
+        metrics: RpcServerMetrics,
+        req: &http::Request<B>,
+        request_size: crate::observe::body::ReadBytes,
+    ) -> Self {
+        let cx = opentelemetry::Context::current();
+        let attributes = rpc_attributes(req.uri().path());
+        metrics.active_requests.add(&cx, 1, &attributes);
+        Self {
+            metrics,
+            attributes,
+            start: std::time::Instant::now(),
+            code: None,
+            request_size,
+            response_size: 0,
+        }
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let cx = &opentelemetry::Context::current();
+        self.metrics.active_requests.add(cx, -1, &self.attributes);
+
+        // NOTE: status を受け取る前に drop された場合はクライアントによるキャンセルとみなす
+        let code = self.code.unwrap_or(tonic::Code::Cancelled);
+        let mut attributes = self.attributes.clone();
+        attributes.push(opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::trace::RPC_GRPC_STATUS_CODE,
+            code as i64,
+        ));
+        let latency = self.start.elapsed().as_secs_f64() * 1000.0;
+        self.metrics.duration.record(cx, latency, &attributes);
+        // NOTE: ストリームの場合もメッセージごとではなく、呼び出し全体のサイズを 1 回だけ記録する
+        self.metrics
+            .request_size
+            .record(cx, self.request_size.get(), &attributes);
+        self.metrics
+            .response_size
+            .record(cx, self.response_size, &attributes);
+    }
+}
+
+fn rpc_attributes(full_method: &str) -> Vec<opentelemetry::KeyValue> {
+    let mut attributes = vec![opentelemetry::KeyValue::new(
+        opentelemetry_semantic_conventions::trace::RPC_SYSTEM,
+        "grpc",
+    )];
+    let methods: Vec<_> = full_method.split('/').collect();
+    if let (Some(service), Some(method)) = (methods.get(1), methods.get(2)) {
+        attributes.push(opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::trace::RPC_SERVICE,
+            service.to_string(),
+        ));
+        attributes.push(opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::trace::RPC_METHOD,
+            method.to_string(),
+        ));
+    }
+    attributes
+}
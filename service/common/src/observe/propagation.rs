@@ -0,0 +1,80 @@
+// read more: https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#general-sdk-configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagator {
+    TraceContext,
+    Baggage,
+    B3,
+    B3Multi,
+    Jaeger,
+}
+
+impl Propagator {
+    // NOTE: 不明な名前は設定のエラーとして集め、残りの propagator だけを使う
+    pub(crate) fn load(sources: &mut crate::config::source::Sources, name: &str) -> Vec<Self> {
+        sources
+            .list(name)
+            .iter()
+            .filter_map(|value| match value.parse() {
+                Ok(propagator) => Some(propagator),
+                Err(e) => {
+                    sources.invalid(name, format!("{}", e));
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::str::FromStr for Propagator {
+    type Err = UnknownPropagatorError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tracecontext" => Ok(Self::TraceContext),
+            "baggage" => Ok(Self::Baggage),
+            "b3" => Ok(Self::B3),
+            "b3multi" => Ok(Self::B3Multi),
+            "jaeger" => Ok(Self::Jaeger),
+            _ => Err(UnknownPropagatorError(name.to_string())),
+        }
+    }
+}
+
+pub fn propagator(
+    propagators: &[Propagator],
+) -> opentelemetry::sdk::propagation::TextMapCompositePropagator {
+    let propagators = propagators
+        .iter()
+        .map(|propagator| text_map_propagator(*propagator))
+        .collect();
+    opentelemetry::sdk::propagation::TextMapCompositePropagator::new(propagators)
+}
+
+fn text_map_propagator(
+    propagator: Propagator,
+) -> Box<dyn opentelemetry::propagation::TextMapPropagator + Send + Sync> {
+    match propagator {
+        Propagator::TraceContext => {
+            Box::new(opentelemetry::sdk::propagation::TraceContextPropagator::new())
+        }
+        Propagator::Baggage => Box::new(opentelemetry::sdk::propagation::BaggagePropagator::new()),
+        Propagator::B3 => Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+            opentelemetry_zipkin::B3Encoding::SingleHeader,
+        )),
+        Propagator::B3Multi => Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+            opentelemetry_zipkin::B3Encoding::MultipleHeader,
+        )),
+        Propagator::Jaeger => Box::new(opentelemetry_jaeger::Propagator::new()),
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownPropagatorError(String);
+
+impl std::fmt::Display for UnknownPropagatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown propagator: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownPropagatorError {}
@@ -0,0 +1,197 @@
+use opentelemetry::sdk::resource::ResourceDetector;
+
+const DETECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+// NOTE: 後に merge したものが優先される
+// デフォルト値 < 検出した値 < OTEL_RESOURCE_ATTRIBUTES < OTEL_SERVICE_NAME
+pub fn resource(
+    service: super::Service,
+    config: &super::OpenTelemetry,
+) -> opentelemetry::sdk::Resource {
+    let default = opentelemetry::sdk::Resource::from_schema_url(
+        [
+            opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::resource::SERVICE_NAME,
+                service.name,
+            ),
+            opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::resource::SERVICE_VERSION,
+                service.version,
+            ),
+        ],
+        config.schema_url.clone(),
+    );
+    let detected = opentelemetry::sdk::Resource::from_detectors(
+        DETECTION_TIMEOUT,
+        vec![
+            Box::new(HostResourceDetector),
+            Box::new(opentelemetry::sdk::resource::OsResourceDetector),
+            Box::new(opentelemetry::sdk::resource::ProcessResourceDetector),
+            Box::new(ContainerResourceDetector),
+            Box::new(DeploymentEnvironmentDetector(
+                config.deployment_environment.clone(),
+            )),
+            Box::new(opentelemetry::sdk::resource::EnvResourceDetector::new()),
+        ],
+    );
+    let resource = default.merge(&detected);
+
+    match &config.service_name {
+        Some(service_name) => resource.merge(&opentelemetry::sdk::Resource::new([
+            opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::resource::SERVICE_NAME,
+                service_name.clone(),
+            ),
+        ])),
+        None => resource,
+    }
+}
+
+#[derive(Debug)]
+struct HostResourceDetector;
+
+impl ResourceDetector for HostResourceDetector {
+    fn detect(&self, _timeout: std::time::Duration) -> opentelemetry::sdk::Resource {
+        let mut attributes = vec![opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::resource::HOST_ARCH,
+            std::env::consts::ARCH,
+        )];
+        if let Some(host_name) = host_name() {
+            attributes.push(opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::resource::HOST_NAME,
+                host_name,
+            ));
+        }
+        opentelemetry::sdk::Resource::new(attributes)
+    }
+}
+
+fn host_name() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[derive(Debug)]
+struct ContainerResourceDetector;
+
+impl ResourceDetector for ContainerResourceDetector {
+    fn detect(&self, _timeout: std::time::Duration) -> opentelemetry::sdk::Resource {
+        let container_id = std::fs::read_to_string("/proc/self/cgroup")
+            .ok()
+            .and_then(|cgroup| container_id_from_cgroup(&cgroup))
+            .or_else(|| {
+                std::fs::read_to_string("/proc/self/mountinfo")
+                    .ok()
+                    .and_then(|mountinfo| container_id_from_mountinfo(&mountinfo))
+            });
+        match container_id {
+            Some(container_id) => {
+                opentelemetry::sdk::Resource::new([opentelemetry::KeyValue::new(
+                    opentelemetry_semantic_conventions::resource::CONTAINER_ID,
+                    container_id,
+                )])
+            }
+            None => opentelemetry::sdk::Resource::empty(),
+        }
+    }
+}
+
+// cgroup v1: `12:pids:/docker/<id>`
+// systemd:   `0::/system.slice/docker-<id>.scope`
+fn container_id_from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let segment = line.rsplit('/').next()?.trim_end_matches(".scope");
+        let id = segment.rsplit(['-', ':']).next()?;
+        is_container_id(id).then(|| id.to_string())
+    })
+}
+
+// cgroup v2 では /proc/self/cgroup に ID が現れないため mountinfo から探す
+// e.g. `/var/lib/docker/containers/<id>/hostname`
+fn container_id_from_mountinfo(mountinfo: &str) -> Option<String> {
+    mountinfo.lines().find_map(|line| {
+        let mut segments = line.split('/').skip_while(|s| *s != "containers");
+        segments.next()?;
+        let id = segments.next()?;
+        is_container_id(id).then(|| id.to_string())
+    })
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[derive(Debug)]
+struct DeploymentEnvironmentDetector(Option<String>);
+
+impl ResourceDetector for DeploymentEnvironmentDetector {
+    fn detect(&self, _timeout: std::time::Duration) -> opentelemetry::sdk::Resource {
+        match &self.0 {
+            Some(environment) => opentelemetry::sdk::Resource::new([opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::resource::DEPLOYMENT_ENVIRONMENT,
+                environment.clone(),
+            )]),
+            None => opentelemetry::sdk::Resource::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const ID: &str = "2f1d3a0c9b8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a392817060504";
+
+    #[test]
+    fn container_id_from_cgroup_v1() {
+        let cgroup = format!("13:name=systemd:/\n12:pids:/docker/{}\n", ID);
+        assert_eq!(
+            super::container_id_from_cgroup(&cgroup),
+            Some(ID.to_string())
+        );
+    }
+
+    #[test]
+    fn container_id_from_systemd_scope() {
+        let cgroup = format!("0::/system.slice/docker-{}.scope\n", ID);
+        assert_eq!(
+            super::container_id_from_cgroup(&cgroup),
+            Some(ID.to_string())
+        );
+    }
+
+    #[test]
+    fn container_id_from_cri_containerd() {
+        let cgroup = format!(
+            "0::/kubepods.slice/kubepods-pod1.slice/cri-containerd:{}\n",
+            ID
+        );
+        assert_eq!(
+            super::container_id_from_cgroup(&cgroup),
+            Some(ID.to_string())
+        );
+    }
+
+    #[test]
+    fn no_container_id_outside_a_container() {
+        assert_eq!(super::container_id_from_cgroup("0::/\n"), None);
+        assert_eq!(
+            super::container_id_from_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn container_id_from_mountinfo() {
+        let mountinfo = format!(
+            "651 632 254:1 /var/lib/docker/containers/{}/hostname /etc/hostname rw,relatime - ext4 /dev/vda1 rw\n",
+            ID
+        );
+        assert_eq!(
+            super::container_id_from_mountinfo(&mountinfo),
+            Some(ID.to_string())
+        );
+    }
+}
@@ -0,0 +1,47 @@
+// NOTE: provider を作り直さずに sampling ratio を変更できるよう、sampler を差し替え可能にする
+#[derive(Debug, Clone)]
+pub struct ReloadableSampler(std::sync::Arc<std::sync::RwLock<opentelemetry::sdk::trace::Sampler>>);
+
+impl ReloadableSampler {
+    pub fn new(ratio: f64) -> Self {
+        Self(std::sync::Arc::new(std::sync::RwLock::new(sampler(ratio))))
+    }
+
+    // NOTE: lock を持ったまま panic しても sampler 自体は壊れないため、poison は無視して使い続ける
+    pub fn set_ratio(&self, ratio: f64) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = sampler(ratio);
+    }
+}
+
+// NOTE: 親の sampling の判断に従い、root span だけを ratio で sampling する
+fn sampler(ratio: f64) -> opentelemetry::sdk::trace::Sampler {
+    opentelemetry::sdk::trace::Sampler::ParentBased(Box::new(
+        opentelemetry::sdk::trace::Sampler::TraceIdRatioBased(ratio),
+    ))
+}
+
+impl opentelemetry::sdk::trace::ShouldSample for ReloadableSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&opentelemetry::Context>,
+        trace_id: opentelemetry::trace::TraceId,
+        name: &str,
+        span_kind: &opentelemetry::trace::SpanKind,
+        attributes: &opentelemetry::trace::OrderMap<opentelemetry::Key, opentelemetry::Value>,
+        links: &[opentelemetry::trace::Link],
+        instrumentation_library: &opentelemetry::InstrumentationLibrary,
+    ) -> opentelemetry::trace::SamplingResult {
+        self.0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .should_sample(
+                parent_context,
+                trace_id,
+                name,
+                span_kind,
+                attributes,
+                links,
+                instrumentation_library,
+            )
+    }
+}
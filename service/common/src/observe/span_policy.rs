@@ -0,0 +1,149 @@
+// NOTE: ヘルスチェックや reflection などのリクエストで trace が埋もれないよう、
+// パスに応じて span を作らない (ignore) か debug レベルに落とす (downgrade)
+//
+// パターンの書式
+// - `/foo/bar`       : 完全一致
+// - `/foo/*`         : 前方一致
+// - `regex:^/foo/.+` : 正規表現
+#[derive(Debug, Clone, Default)]
+pub struct SpanPolicy {
+    ignore: Vec<Matcher>,
+    downgrade: Vec<Matcher>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Record,
+    Downgrade,
+    Ignore,
+}
+
+impl SpanPolicy {
+    pub fn new(ignore: &[String], downgrade: &[String]) -> Result<Self, regex::Error> {
+        Ok(Self {
+            ignore: ignore
+                .iter()
+                .map(|p| Matcher::parse(p))
+                .collect::<Result<_, _>>()?,
+            downgrade: downgrade
+                .iter()
+                .map(|p| Matcher::parse(p))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    // NOTE: 不正なパターンは設定のエラーとして集め、残りのパターンだけを使う
+    pub(crate) fn load(
+        sources: &mut crate::config::source::Sources,
+        ignore: &str,
+        downgrade: &str,
+    ) -> Self {
+        Self {
+            ignore: Matcher::load(sources, ignore),
+            downgrade: Matcher::load(sources, downgrade),
+        }
+    }
+
+    pub fn action(&self, path: &str) -> Action {
+        if self.ignore.iter().any(|m| m.is_match(path)) {
+            Action::Ignore
+        } else if self.downgrade.iter().any(|m| m.is_match(path)) {
+            Action::Downgrade
+        } else {
+            Action::Record
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Exact(String),
+    Prefix(String),
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    fn parse(pattern: &str) -> Result<Self, regex::Error> {
+        if let Some(re) = pattern.strip_prefix("regex:") {
+            Ok(Self::Regex(regex::Regex::new(re)?))
+        } else if let Some(prefix) = pattern.strip_suffix('*') {
+            Ok(Self::Prefix(prefix.to_string()))
+        } else {
+            Ok(Self::Exact(pattern.to_string()))
+        }
+    }
+
+    fn load(sources: &mut crate::config::source::Sources, name: &str) -> Vec<Self> {
+        sources
+            .list(name)
+            .iter()
+            .filter_map(|pattern| match Self::parse(pattern) {
+                Ok(matcher) => Some(matcher),
+                Err(e) => {
+                    sources.invalid(name, format!("{}: {}", pattern, e));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            Self::Exact(p) => path == p,
+            Self::Prefix(p) => path.starts_with(p.as_str()),
+            Self::Regex(re) => re.is_match(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn exact_pattern_matches_only_the_same_path() {
+        let matcher = super::Matcher::parse("/grpc.health.v1.Health/Check").unwrap();
+        assert!(matcher.is_match("/grpc.health.v1.Health/Check"));
+        assert!(!matcher.is_match("/grpc.health.v1.Health/Watch"));
+        assert!(!matcher.is_match("/grpc.health.v1.Health/Check/extra"));
+    }
+
+    #[test]
+    fn wildcard_pattern_matches_by_prefix() {
+        let matcher = super::Matcher::parse("/grpc.health.v1.Health/*").unwrap();
+        assert!(matcher.is_match("/grpc.health.v1.Health/Check"));
+        assert!(matcher.is_match("/grpc.health.v1.Health/Watch"));
+        assert!(!matcher.is_match("/grpc.health.v1.HealthCheck"));
+        assert!(!matcher.is_match("/tenant.v1.TenantService/GetTenant"));
+    }
+
+    #[test]
+    fn regex_pattern_matches_by_regex() {
+        let matcher = super::Matcher::parse("regex:^/tenant\\.v1\\.[A-Za-z]+/Get").unwrap();
+        assert!(matcher.is_match("/tenant.v1.TenantService/GetTenant"));
+        assert!(!matcher.is_match("/tenant.v1.TenantService/ListTenants"));
+        assert!(super::Matcher::parse("regex:(").is_err());
+    }
+
+    #[test]
+    fn ignore_takes_precedence_over_downgrade() {
+        let policy = super::SpanPolicy::new(
+            &["/grpc.health.v1.Health/*".to_string()],
+            &[
+                "/grpc.health.v1.Health/Check".to_string(),
+                "/grpc.reflection.v1alpha.ServerReflection/*".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            policy.action("/grpc.health.v1.Health/Check"),
+            super::Action::Ignore
+        );
+        assert_eq!(
+            policy.action("/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo"),
+            super::Action::Downgrade
+        );
+        assert_eq!(
+            policy.action("/tenant.v1.TenantService/GetTenant"),
+            super::Action::Record
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// NOTE: 終了した span 数と export できた span 数の差から、
+// キューがあふれたり終了時に送りきれなかったりして欠落した span 数を求める
+#[derive(Debug, Default)]
+pub struct SpanStats {
+    ended: AtomicU64,
+    exported: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl SpanStats {
+    pub fn log_summary(&self) {
+        let ended = self.ended.load(Ordering::SeqCst);
+        let exported = self.exported.load(Ordering::SeqCst);
+        let failed = self.failed.load(Ordering::SeqCst);
+        let dropped = ended.saturating_sub(exported + failed);
+        if failed > 0 || dropped > 0 {
+            tracing::warn!(
+                "spans: ended={}, exported={}, failed={}, dropped={}",
+                ended,
+                exported,
+                failed,
+                dropped
+            );
+        } else {
+            tracing::info!("spans: ended={}, exported={}", ended, exported);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CountingSpanProcessor(pub std::sync::Arc<SpanStats>);
+
+impl opentelemetry::sdk::trace::SpanProcessor for CountingSpanProcessor {
+    fn on_start(&self, _span: &mut opentelemetry::sdk::trace::Span, _cx: &opentelemetry::Context) {}
+
+    fn on_end(&self, span: opentelemetry::sdk::export::trace::SpanData) {
+        // NOTE: BatchSpanProcessor と同様に sample されたものだけを数える
+        if span.span_context.is_sampled() {
+            self.0.ended.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct CountingSpanExporter<E> {
+    inner: E,
+    stats: std::sync::Arc<SpanStats>,
+}
+
+impl<E> CountingSpanExporter<E> {
+    pub fn new(inner: E, stats: std::sync::Arc<SpanStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+impl<E> opentelemetry::sdk::export::trace::SpanExporter for CountingSpanExporter<E>
+where
+    E: opentelemetry::sdk::export::trace::SpanExporter,
+{
+    fn export(
+        &mut self,
+        batch: Vec<opentelemetry::sdk::export::trace::SpanData>,
+    ) -> std::pin::Pin<
+        Box<
+            dyn std::future::Future<Output = opentelemetry::sdk::export::trace::ExportResult>
+                + Send,
+        >,
+    > {
+        let count = batch.len() as u64;
+        let stats = self.stats.clone();
+        let export = self.inner.export(batch);
+        Box::pin(async move {
+            let result = export.await;
+            match &result {
+                Ok(()) => stats.exported.fetch_add(count, Ordering::SeqCst),
+                Err(_) => stats.failed.fetch_add(count, Ordering::SeqCst),
+            };
+            result
+        })
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown()
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type Channel =
+    crate::observe::middleware::grpc_client::GrpcClientService<tonic::transport::Channel>;
+
+const TENANT_SERVICE_NAME: &str = "tenant.v1.TenantService";
+
+// NOTE: item の作成ごとに TenantService を呼ばないよう、存在を確認できた tenant の quota を一定時間キャッシュする
+#[derive(Clone)]
+pub struct TenantClient {
+    client: proto::tenant::v1::tenant_service_client::TenantServiceClient<Channel>,
+    // NOTE: readiness のチェックのたびに trace が作られないよう tracing middleware のない channel を使う
+    health: tonic_health::pb::health_client::HealthClient<tonic::transport::Channel>,
+    cache: Arc<tokio::sync::Mutex<HashMap<ulid::Ulid, CachedTenant>>>,
+    cache_ttl: std::time::Duration,
+}
+
+#[derive(Debug, Clone)]
+struct CachedTenant {
+    quota: proto::tenant::v1::Quota,
+    cached_at: std::time::Instant,
+}
+
+impl std::fmt::Debug for TenantClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TenantClient")
+            .field("cache_ttl", &self.cache_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TenantClient {
+    pub fn new(config: &crate::config::TenantService) -> Result<Self, tonic::transport::Error> {
+        let endpoint =
+            tonic::transport::Endpoint::from_shared(config.url.clone())?.timeout(config.timeout);
+        let raw_channel = endpoint.connect_lazy();
+        let channel = tower::ServiceBuilder::new()
+            .layer(crate::observe::middleware::grpc_client::GrpcClientLayer::new(&endpoint))
+            .service(raw_channel.clone());
+        Ok(Self {
+            client: proto::tenant::v1::tenant_service_client::TenantServiceClient::new(channel),
+            health: tonic_health::pb::health_client::HealthClient::new(raw_channel),
+            cache: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            cache_ttl: config.cache_ttl,
+        })
+    }
+
+    // NOTE: tenant が存在しない場合は FAILED_PRECONDITION を返す
+    #[tracing::instrument(skip(self))]
+    pub async fn tenant_quota(
+        &self,
+        id: ulid::Ulid,
+    ) -> Result<proto::tenant::v1::Quota, tonic::Status> {
+        {
+            let cache = self.cache.lock().await;
+            if let Some(cached) = cache.get(&id) {
+                if cached.cached_at.elapsed() < self.cache_ttl {
+                    return Ok(cached.quota.clone());
+                }
+            }
+        }
+
+        let req = proto::tenant::v1::GetTenantRequest {
+            id: Some(proto::lib::v1::Ulid {
+                value: id.to_string(),
+            }),
+        };
+        match self.client.clone().get_tenant(req).await {
+            Ok(res) => {
+                let quota = res
+                    .into_inner()
+                    .tenant
+                    .and_then(|tenant| tenant.quota)
+                    .unwrap_or_default();
+                let mut cache = self.cache.lock().await;
+                cache.retain(|_, cached| cached.cached_at.elapsed() < self.cache_ttl);
+                cache.insert(
+                    id,
+                    CachedTenant {
+                        quota: quota.clone(),
+                        cached_at: std::time::Instant::now(),
+                    },
+                );
+                Ok(quota)
+            }
+            Err(status) if status.code() == tonic::Code::NotFound => Err(
+                tonic::Status::failed_precondition(format!("tenant not found: {}", id)),
+            ),
+            Err(status) => {
+                tracing::error!("{}", status.to_string());
+                Err(tonic::Status::unavailable(format!(
+                    "failed to get tenant: {}",
+                    status.message()
+                )))
+            }
+        }
+    }
+
+    // NOTE: TenantService の gRPC health を問い合わせ、SERVING でなければエラーにする
+    pub async fn check_health(&self) -> Result<(), String> {
+        let req = tonic_health::pb::HealthCheckRequest {
+            service: TENANT_SERVICE_NAME.to_string(),
+        };
+        let res = self
+            .health
+            .clone()
+            .check(req)
+            .await
+            .map_err(|status| status.to_string())?;
+        match res.into_inner().status() {
+            tonic_health::pb::health_check_response::ServingStatus::Serving => Ok(()),
+            status => Err(format!(
+                "{} is {}",
+                TENANT_SERVICE_NAME,
+                status.as_str_name()
+            )),
+        }
+    }
+
+    // NOTE: tenant のイベントを受け取った際にキャッシュを更新する
+    pub async fn mark_tenant_exists(&self, id: ulid::Ulid, quota: proto::tenant::v1::Quota) {
+        let mut cache = self.cache.lock().await;
+        cache.insert(
+            id,
+            CachedTenant {
+                quota,
+                cached_at: std::time::Instant::now(),
+            },
+        );
+    }
+
+    pub async fn forget_tenant(&self, id: ulid::Ulid) {
+        let mut cache = self.cache.lock().await;
+        cache.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // NOTE: 接続できない URL を使い、TenantService を呼んだかどうかをエラーで判定する
+    fn client(cache_ttl: std::time::Duration) -> super::TenantClient {
+        super::TenantClient::new(&crate::config::TenantService {
+            url: "http://127.0.0.1:1".to_string(),
+            timeout: std::time::Duration::from_secs(1),
+            cache_ttl,
+        })
+        .unwrap()
+    }
+
+    fn quota(max_items: u64) -> proto::tenant::v1::Quota {
+        proto::tenant::v1::Quota {
+            max_items,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_tenant_is_returned_without_calling_tenant_service() {
+        let client = client(std::time::Duration::from_secs(60));
+        let id = ulid::Ulid::new();
+        client.mark_tenant_exists(id, quota(10)).await;
+        assert_eq!(client.tenant_quota(id).await.unwrap(), quota(10));
+    }
+
+    #[tokio::test]
+    async fn expired_tenant_is_fetched_again() {
+        let client = client(std::time::Duration::from_millis(20));
+        let id = ulid::Ulid::new();
+        client.mark_tenant_exists(id, quota(10)).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let status = client.tenant_quota(id).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn forgotten_tenant_is_fetched_again() {
+        let client = client(std::time::Duration::from_secs(60));
+        let id = ulid::Ulid::new();
+        client.mark_tenant_exists(id, quota(10)).await;
+        client.forget_tenant(id).await;
+        let status = client.tenant_quota(id).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+    }
+}
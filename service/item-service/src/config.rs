@@ -1,32 +1,157 @@
-const OPEN_TELEMETRY_SCHEMA_URL_KEY: &str = "OPEN_TELEMETRY_SCHEMA_URL";
-const OPEN_TELEMETRY_ENDPOINT_KEY: &str = "OPEN_TELEMETRY_ENDPOINT";
+use common::config::reload::Reloadable as _;
+use common::config::source;
+
+pub use common::config::ConfigError;
+
+const KEYS: &[source::Key] = &[
+    source::Key {
+        name: "port",
+        env: &["ITEM_SERVICE_PORT"],
+        default: None,
+    },
+    source::Key {
+        name: "grpc_port",
+        env: &["ITEM_SERVICE_GRPC_PORT"],
+        default: None,
+    },
+    source::Key {
+        name: "tenant_service.url",
+        env: &["TENANT_SERVICE_URL"],
+        default: None,
+    },
+    source::Key {
+        name: "tenant_service.timeout_seconds",
+        env: &["TENANT_SERVICE_TIMEOUT_SECONDS"],
+        default: Some("5"),
+    },
+    source::Key {
+        name: "tenant_service.cache_ttl_seconds",
+        env: &["TENANT_CACHE_TTL_SECONDS"],
+        default: Some("60"),
+    },
+    source::Key {
+        name: "event_broker_dir",
+        env: &["EVENT_BROKER_DIR"],
+        default: None,
+    },
+    source::Key {
+        name: "log.filter",
+        env: &["RUST_LOG"],
+        default: Some("info"),
+    },
+    source::Key {
+        name: "drain_timeout_seconds",
+        env: &["SHUTDOWN_DRAIN_TIMEOUT_SECONDS"],
+        default: Some("30"),
+    },
+    source::Key {
+        name: "otel.schema_url",
+        env: &["OPEN_TELEMETRY_SCHEMA_URL"],
+        default: None,
+    },
+    // NOTE: 標準の OTEL_EXPORTER_OTLP_ENDPOINT を優先し、なければ独自の変数を使う
+    source::Key {
+        name: "otel.endpoint",
+        env: &["OTEL_EXPORTER_OTLP_ENDPOINT", "OPEN_TELEMETRY_ENDPOINT"],
+        default: None,
+    },
+    source::Key {
+        name: "otel.service_name",
+        env: &["OTEL_SERVICE_NAME"],
+        default: None,
+    },
+    source::Key {
+        name: "otel.deployment_environment",
+        env: &["DEPLOYMENT_ENVIRONMENT"],
+        default: None,
+    },
+    source::Key {
+        name: "otel.propagators",
+        env: &["OTEL_PROPAGATORS"],
+        default: Some("tracecontext,baggage"),
+    },
+    source::Key {
+        name: "otel.span_policy.ignore",
+        env: &["SPAN_POLICY_IGNORE"],
+        default: Some("/healthz,/livez,/readyz,/metrics,/grpc.health.v1.Health/*"),
+    },
+    source::Key {
+        name: "otel.span_policy.downgrade",
+        env: &["SPAN_POLICY_DOWNGRADE"],
+        default: Some("/grpc.reflection.v1alpha.ServerReflection/*"),
+    },
+    source::Key {
+        name: "otel.sampling_ratio",
+        env: &["OTEL_TRACES_SAMPLER_ARG"],
+        default: Some("1.0"),
+    },
+    source::Key {
+        name: "otel.shutdown_timeout_seconds",
+        env: &["OTEL_SHUTDOWN_TIMEOUT_SECONDS"],
+        default: Some("5"),
+    },
+];
+
+pub enum Loaded {
+    Run(Box<Config>),
+    // NOTE: --print-config が指定された場合は設定を出力して終了する
+    Printed,
+}
 
 pub struct Config {
-    pub otel: OpenTelemetry,
+    pub port: u16,
+    pub grpc_port: u16,
+    pub otel: common::observe::OpenTelemetry,
+    pub tenant_service: TenantService,
+    pub event_broker_dir: String,
+    pub drain_timeout: std::time::Duration,
+    pub runtime: common::observe::Runtime,
+    args: source::Args,
 }
 
 impl Config {
-    pub fn from_env() -> Self {
-        Self {
-            otel: OpenTelemetry::from_env(),
+    // NOTE: 既定値、設定ファイル (--config)、環境変数、CLI の順に読み込む
+    pub fn load(args: impl Iterator<Item = String>) -> Result<Loaded, ConfigError> {
+        let args = source::Args::parse(args)?;
+        let mut sources = source::Sources::load(KEYS, &args);
+        let config = Self {
+            port: sources.required("port"),
+            grpc_port: sources.required("grpc_port"),
+            otel: common::observe::OpenTelemetry::load(&mut sources),
+            tenant_service: TenantService::load(&mut sources),
+            event_broker_dir: sources.required("event_broker_dir"),
+            drain_timeout: sources.seconds("drain_timeout_seconds"),
+            runtime: common::observe::Runtime::load(&mut sources),
+            args,
+        };
+        // NOTE: 不正な設定を出力しないよう、検証してから出力する
+        let rendered = sources.render();
+        sources.finish()?;
+        if config.args.print_config {
+            print!("{}", rendered);
+            return Ok(Loaded::Printed);
         }
+        Ok(Loaded::Run(Box::new(config)))
+    }
+
+    // NOTE: 設定ファイルの変更を監視し、再読み込みできる設定の変更を通知する
+    pub fn watch_runtime(&self) -> tokio::sync::watch::Receiver<common::observe::Runtime> {
+        common::config::reload::spawn(KEYS, &self.args, self.runtime.clone())
     }
 }
 
-pub struct OpenTelemetry {
-    pub schema_url: String,
-    pub endpoint: String,
+pub struct TenantService {
+    pub url: String,
+    pub timeout: std::time::Duration,
+    pub cache_ttl: std::time::Duration,
 }
 
-impl OpenTelemetry {
-    fn from_env() -> Self {
+impl TenantService {
+    fn load(sources: &mut source::Sources) -> Self {
         Self {
-            schema_url: from_env(OPEN_TELEMETRY_SCHEMA_URL_KEY),
-            endpoint: from_env(OPEN_TELEMETRY_ENDPOINT_KEY),
+            url: sources.required("tenant_service.url"),
+            timeout: sources.seconds("tenant_service.timeout_seconds"),
+            cache_ttl: sources.seconds("tenant_service.cache_ttl_seconds"),
         }
     }
 }
-
-fn from_env(key: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| panic!("{} must be set", key))
-}
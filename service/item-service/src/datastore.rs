@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// NOTE: ID の順に一覧を返せるよう BTreeMap で保持する
+#[derive(Debug, Clone)]
+pub struct InMemory {
+    items: Arc<tokio::sync::Mutex<BTreeMap<ulid::Ulid, crate::service::item::model::Item>>>,
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        Self {
+            items: Arc::new(tokio::sync::Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    // NOTE: 上限の確認と追加を同じロックの中で行う (max_items が 0 の場合は無制限)
+    // 追加後の件数を返し、上限に達している場合は現在の件数をエラーとして返す
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_item(
+        &self,
+        id: ulid::Ulid,
+        item: crate::service::item::model::Item,
+        max_items: u64,
+    ) -> Result<u64, u64> {
+        let mut items = self.items.lock().await;
+        let count = count_items(&items, item.tenant_id);
+        if max_items > 0 && count >= max_items {
+            return Err(count);
+        }
+        items.insert(id, item);
+        Ok(count + 1)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn count_items(&self, tenant_id: ulid::Ulid) -> u64 {
+        let items = self.items.lock().await;
+        count_items(&items, tenant_id)
+    }
+
+    // NOTE: アーカイブされた item は削除された tenant のものなので、Get / Update / Delete では存在しないものとして扱う
+    #[tracing::instrument(skip_all)]
+    pub async fn get_item(&self, id: ulid::Ulid) -> Option<crate::service::item::model::Item> {
+        let items = self.items.lock().await;
+        items.get(&id).filter(|item| !item.archived).cloned()
+    }
+
+    // NOTE: after より後ろの ID を最大 limit 件返す (アーカイブされた item は含めない)
+    #[tracing::instrument(skip_all)]
+    pub async fn list_items(
+        &self,
+        tenant_id: Option<ulid::Ulid>,
+        after: Option<ulid::Ulid>,
+        limit: usize,
+    ) -> Vec<crate::service::item::model::Item> {
+        let items = self.items.lock().await;
+        let range = match after {
+            Some(after) => {
+                items.range((std::ops::Bound::Excluded(after), std::ops::Bound::Unbounded))
+            }
+            None => items.range(..),
+        };
+        range
+            .map(|(_, item)| item)
+            .filter(|item| !item.archived)
+            .filter(|item| tenant_id.is_none_or(|tenant_id| item.tenant_id == tenant_id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn update_item(
+        &self,
+        id: ulid::Ulid,
+        f: impl FnOnce(&mut crate::service::item::model::Item),
+    ) -> Option<crate::service::item::model::Item> {
+        let mut items = self.items.lock().await;
+        let item = items.get_mut(&id).filter(|item| !item.archived)?;
+        f(item);
+        Some(item.clone())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_item(&self, id: ulid::Ulid) -> Option<crate::service::item::model::Item> {
+        let mut items = self.items.lock().await;
+        if items.get(&id)?.archived {
+            return None;
+        }
+        items.remove(&id)
+    }
+
+    // NOTE: 削除された tenant の item は消さずにアーカイブする
+    #[tracing::instrument(skip_all)]
+    pub async fn archive_items_by_tenant(&self, tenant_id: ulid::Ulid) -> usize {
+        let mut items = self.items.lock().await;
+        items
+            .values_mut()
+            .filter(|item| item.tenant_id == tenant_id && !item.archived)
+            .map(|item| item.archived = true)
+            .count()
+    }
+}
+
+// NOTE: アーカイブされた item は quota の対象に含めない
+fn count_items(
+    items: &BTreeMap<ulid::Ulid, crate::service::item::model::Item>,
+    tenant_id: ulid::Ulid,
+) -> u64 {
+    items
+        .values()
+        .filter(|item| item.tenant_id == tenant_id && !item.archived)
+        .count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    fn item(tenant_id: ulid::Ulid) -> crate::service::item::model::Item {
+        crate::service::item::model::Item::new(tenant_id, "item".to_string(), String::new(), 100)
+    }
+
+    async fn insert(
+        datastore: &super::InMemory,
+        tenant_id: ulid::Ulid,
+        max_items: u64,
+    ) -> Result<u64, u64> {
+        let item = item(tenant_id);
+        datastore.insert_item(item.id, item, max_items).await
+    }
+
+    #[tokio::test]
+    async fn insert_item_enforces_max_items_per_tenant() {
+        let datastore = super::InMemory::new();
+        let tenant_id = ulid::Ulid::new();
+        assert_eq!(insert(&datastore, tenant_id, 2).await, Ok(1));
+        assert_eq!(insert(&datastore, tenant_id, 2).await, Ok(2));
+        assert_eq!(insert(&datastore, tenant_id, 2).await, Err(2));
+        assert_eq!(datastore.count_items(tenant_id).await, 2);
+
+        // NOTE: 他の tenant の item は数えない
+        assert_eq!(insert(&datastore, ulid::Ulid::new(), 2).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn zero_max_items_means_unlimited() {
+        let datastore = super::InMemory::new();
+        let tenant_id = ulid::Ulid::new();
+        for count in 1..=100 {
+            assert_eq!(insert(&datastore, tenant_id, 0).await, Ok(count));
+        }
+    }
+
+    #[tokio::test]
+    async fn archived_items_do_not_count_and_are_hidden() {
+        let datastore = super::InMemory::new();
+        let tenant_id = ulid::Ulid::new();
+        let archived = item(tenant_id);
+        let archived_id = archived.id;
+        datastore
+            .insert_item(archived_id, archived, 1)
+            .await
+            .unwrap();
+        assert_eq!(datastore.archive_items_by_tenant(tenant_id).await, 1);
+        assert_eq!(datastore.archive_items_by_tenant(tenant_id).await, 0);
+
+        assert_eq!(datastore.count_items(tenant_id).await, 0);
+        assert_eq!(insert(&datastore, tenant_id, 1).await, Ok(1));
+
+        assert!(datastore.get_item(archived_id).await.is_none());
+        assert!(datastore.update_item(archived_id, |_| {}).await.is_none());
+        assert!(datastore.delete_item(archived_id).await.is_none());
+        let items = datastore.list_items(Some(tenant_id), None, 10).await;
+        assert_eq!(items.len(), 1);
+        assert_ne!(items[0].id, archived_id);
+    }
+}
@@ -0,0 +1,164 @@
+use opentelemetry::trace::TraceContextExt as _;
+use prost::Message as _;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+use tracing::Instrument as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use common::observe::LOG_LEVEL;
+
+pub const TENANT_EVENTS_TOPIC: &str = "tenant-events";
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// NOTE: tenant-service がファイルに追記したイベントを末尾まで読み進める
+// オフセットはメモリ上にしか持たないため、再起動時は先頭から読み直す (handler は冪等にする)
+pub struct FileConsumer {
+    topic: &'static str,
+    path: std::path::PathBuf,
+    offset: u64,
+}
+
+impl FileConsumer {
+    pub fn new(dir: impl AsRef<std::path::Path>, topic: &'static str) -> Self {
+        Self {
+            topic,
+            path: dir.as_ref().join(format!("{}.bin", topic)),
+            offset: 0,
+        }
+    }
+
+    pub async fn run(
+        mut self,
+        handler: TenantEventHandler,
+        mut signal_rx: tokio::sync::watch::Receiver<()>,
+    ) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = signal_rx.changed() => return,
+            }
+            let events = match self.poll().await {
+                Ok(events) => events,
+                Err(e) => {
+                    tracing::error!("failed to read {}: {}", self.path.display(), e);
+                    continue;
+                }
+            };
+            for event in events {
+                handler.handle(self.topic, event).await;
+            }
+        }
+    }
+
+    async fn poll(&mut self) -> std::io::Result<Vec<proto::tenant::v1::TenantEvent>> {
+        let mut file = match tokio::fs::File::open(&self.path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        file.seek(std::io::SeekFrom::Start(self.offset)).await?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).await?;
+
+        let mut events = vec![];
+        let mut pos = 0;
+        while pos < buf.len() {
+            let mut rest = &buf[pos..];
+            // NOTE: 書き込み途中のイベントは次回に読む
+            let Ok(len) = prost::decode_length_delimiter(&mut rest) else {
+                break;
+            };
+            if rest.len() < len {
+                break;
+            }
+            let header = buf.len() - pos - rest.len();
+            match proto::tenant::v1::TenantEvent::decode(&rest[..len]) {
+                Ok(event) => events.push(event),
+                Err(e) => tracing::error!("failed to decode tenant event: {}", e),
+            }
+            pos += header + len;
+        }
+        self.offset += pos as u64;
+        Ok(events)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TenantEventHandler {
+    datastore: crate::datastore::InMemory,
+    tenant_client: crate::client::TenantClient,
+    quotas: crate::quota::QuotaEnforcer,
+}
+
+impl TenantEventHandler {
+    pub fn new(
+        datastore: crate::datastore::InMemory,
+        tenant_client: crate::client::TenantClient,
+        quotas: crate::quota::QuotaEnforcer,
+    ) -> Self {
+        Self {
+            datastore,
+            tenant_client,
+            quotas,
+        }
+    }
+
+    async fn handle(&self, topic: &str, event: proto::tenant::v1::TenantEvent) {
+        let span = tracing::span!(
+            LOG_LEVEL,
+            "",
+            otel.name = %format!("{} process", topic),
+            otel.kind = "consumer",
+            messaging.system = "file",
+            messaging.destination.name = topic,
+            messaging.operation = "process",
+            messaging.message.id = tracing::field::Empty,
+        );
+        if let Some(event_id) = &event.event_id {
+            span.record("messaging.message.id", event_id.value.as_str());
+        }
+        // NOTE: 非同期なメッセージングのため、producer の span は親ではなく link として紐付ける
+        let producer_cx =
+            opentelemetry::global::get_text_map_propagator(|p| p.extract(&event.trace_context));
+        let producer_span_context = producer_cx.span().span_context().clone();
+        if producer_span_context.is_valid() {
+            span.add_link(producer_span_context);
+        }
+
+        async {
+            match event.event {
+                Some(proto::tenant::v1::tenant_event::Event::Created(created)) => {
+                    let Some(tenant_id) = parse_ulid(created.tenant_id) else {
+                        return;
+                    };
+                    self.tenant_client
+                        .mark_tenant_exists(tenant_id, created.quota.unwrap_or_default())
+                        .await;
+                }
+                Some(proto::tenant::v1::tenant_event::Event::Deleted(deleted)) => {
+                    let Some(tenant_id) = parse_ulid(deleted.tenant_id) else {
+                        return;
+                    };
+                    self.tenant_client.forget_tenant(tenant_id).await;
+                    self.quotas.forget(tenant_id);
+                    let archived = self.datastore.archive_items_by_tenant(tenant_id).await;
+                    tracing::info!("archived {} items of tenant {}", archived, tenant_id);
+                }
+                None => tracing::warn!("received tenant event without payload"),
+            }
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+fn parse_ulid(id: Option<proto::lib::v1::Ulid>) -> Option<ulid::Ulid> {
+    let id = id?;
+    match id.value.parse() {
+        Ok(id) => Some(id),
+        Err(_) => {
+            tracing::error!("invalid tenant id in event: {}", id.value);
+            None
+        }
+    }
+}
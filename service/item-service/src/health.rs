@@ -0,0 +1,173 @@
+const CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+#[derive(Clone)]
+pub struct Readiness {
+    shutting_down: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    tenant_client: crate::client::TenantClient,
+    event_broker_dir: std::path::PathBuf,
+}
+
+impl Readiness {
+    pub fn new(
+        tenant_client: crate::client::TenantClient,
+        event_broker_dir: impl Into<std::path::PathBuf>,
+    ) -> Self {
+        Self {
+            shutting_down: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            tenant_client,
+            event_broker_dir: event_broker_dir.into(),
+        }
+    }
+
+    pub fn shutdown(&self) {
+        self.shutting_down
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        tracing::info!("readiness changed to not ready");
+    }
+
+    // NOTE: collector に送れなくてもリクエストは処理できるため、telemetry の送信先は見ない
+    // TenantService と broker は使えなくても item の取得などは処理できるため、
+    // 結果は返すが ready の判定には含めない
+    async fn check(&self) -> Vec<Check> {
+        let (tenant_service, event_broker_dir) =
+            tokio::join!(self.check_tenant_service(), self.check_event_broker_dir());
+        vec![
+            Check::required(
+                "shutdown",
+                if self.shutting_down.load(std::sync::atomic::Ordering::SeqCst) {
+                    Err("shutting down".to_string())
+                } else {
+                    Ok(())
+                },
+            ),
+            Check::optional("tenant_service", tenant_service),
+            Check::optional("event_broker_dir", event_broker_dir),
+        ]
+    }
+
+    async fn check_tenant_service(&self) -> Result<(), String> {
+        tokio::time::timeout(CHECK_TIMEOUT, self.tenant_client.check_health())
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    // NOTE: tenant-service と共有するディレクトリのため、ファイルを作成できるかで確認する
+    async fn check_event_broker_dir(&self) -> Result<(), String> {
+        let probe = self
+            .event_broker_dir
+            .join(format!(".readyz-{}", ulid::Ulid::new()));
+        tokio::fs::write(&probe, b"")
+            .await
+            .map_err(|e| format!("{}: {}", self.event_broker_dir.display(), e))?;
+        tokio::fs::remove_file(&probe)
+            .await
+            .map_err(|e| format!("{}: {}", probe.display(), e))
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ReadyzResponse {
+    ready: bool,
+    checks: Vec<Check>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Check {
+    name: &'static str,
+    ok: bool,
+    // NOTE: false のチェックは失敗しても ready のままにする
+    required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn required(name: &'static str, result: Result<(), String>) -> Self {
+        Self {
+            name,
+            ok: result.is_ok(),
+            required: true,
+            error: result.err(),
+        }
+    }
+
+    fn optional(name: &'static str, result: Result<(), String>) -> Self {
+        Self {
+            required: false,
+            ..Self::required(name, result)
+        }
+    }
+}
+
+// NOTE: プロセスが応答できるかだけを見る
+// 依存先の障害で再起動されないよう、依存先のチェックは /readyz で行う
+pub async fn livez() -> axum::http::StatusCode {
+    axum::http::StatusCode::OK
+}
+
+pub async fn readyz(
+    axum::extract::State(readiness): axum::extract::State<Readiness>,
+) -> (axum::http::StatusCode, axum::Json<ReadyzResponse>) {
+    let checks = readiness.check().await;
+    let ready = checks.iter().all(|c| c.ok || !c.required);
+    for check in checks.iter().filter(|c| !c.ok) {
+        tracing::warn!(
+            "readiness check failed: {}: {}",
+            check.name,
+            check.error.as_deref().unwrap_or_default()
+        );
+    }
+    let status = if ready {
+        axum::http::StatusCode::OK
+    } else {
+        axum::http::StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, axum::Json(ReadyzResponse { ready, checks }))
+}
+
+#[cfg(test)]
+mod tests {
+    fn readiness(event_broker_dir: &std::path::Path) -> super::Readiness {
+        let tenant_client = crate::client::TenantClient::new(&crate::config::TenantService {
+            url: "http://127.0.0.1:1".to_string(),
+            timeout: std::time::Duration::from_secs(1),
+            cache_ttl: std::time::Duration::from_secs(60),
+        })
+        .unwrap();
+        super::Readiness::new(tenant_client, event_broker_dir)
+    }
+
+    fn failed(checks: &[super::Check]) -> Vec<&'static str> {
+        checks.iter().filter(|c| !c.ok).map(|c| c.name).collect()
+    }
+
+    #[tokio::test]
+    async fn dependency_failures_are_reported_without_gating_readiness() {
+        let dir = std::env::temp_dir().join(format!("readyz-{}", ulid::Ulid::new()));
+        let readiness = readiness(&dir);
+
+        let (status, axum::Json(res)) =
+            super::readyz(axum::extract::State(readiness.clone())).await;
+        assert_eq!(status, axum::http::StatusCode::OK);
+        assert!(res.ready);
+        assert_eq!(failed(&res.checks), ["tenant_service", "event_broker_dir"]);
+
+        std::fs::create_dir(&dir).unwrap();
+        let checks = readiness.check().await;
+        assert_eq!(failed(&checks), ["tenant_service"]);
+        // NOTE: 確認用のファイルは残さない
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_makes_it_not_ready() {
+        let readiness = readiness(&std::env::temp_dir());
+        readiness.shutdown();
+
+        let (status, axum::Json(res)) = super::readyz(axum::extract::State(readiness)).await;
+        assert_eq!(status, axum::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!res.ready);
+        assert!(failed(&res.checks).contains(&"shutdown"));
+    }
+}
@@ -1,18 +1,47 @@
 use axum::{http::StatusCode, routing::get, Router};
 use tower_http::catch_panic::CatchPanicLayer;
 
+mod client;
 mod config;
+mod datastore;
+mod event;
+mod health;
 mod observe;
+mod quota;
+mod service;
+
+const READINESS_DRAIN_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = config::Config::from_env();
-    let shutdown_tracer = observe::init(&config.otel.schema_url, &config.otel.endpoint)
+    let config = match config::Config::load(std::env::args().skip(1)) {
+        Ok(config::Loaded::Run(config)) => *config,
+        Ok(config::Loaded::Printed) => return Ok(()),
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let service = common::observe::Service {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+    };
+    let telemetry = common::observe::init(service, &config.otel, &config.runtime)
         .unwrap_or_else(|e| panic!("failed to init observer: {}", e));
+    telemetry.apply_runtime_changes(config.watch_runtime());
+
+    let span_policy = config.otel.span_policy.clone();
+
+    let datastore = datastore::InMemory::new();
+    let tenant_client = client::TenantClient::new(&config.tenant_service)?;
+    let quotas = quota::QuotaEnforcer::new();
+    let readiness = health::Readiness::new(tenant_client.clone(), &config.event_broker_dir);
 
     let app = Router::new()
         .route("/healthz", get(|| async { StatusCode::OK }))
-        .route("/panic", get(|| async { panic!("panic occured") }))
+        .route("/livez", get(health::livez))
+        .route("/readyz", get(health::readyz))
+        .route("/panic", get(panic))
         .route(
             "/error",
             get(|| async {
@@ -21,27 +50,76 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }),
         )
         .route("/span", get(span))
-        .layer(observe::middleware::trace_layer())
+        .with_state(readiness.clone())
+        .layer(observe::middleware::trace_layer(span_policy.clone()))
         .layer(CatchPanicLayer::new());
 
-    let port = std::env::var("ITEM_SERVICE_PORT")
-        .unwrap_or_else(|_| panic!("ITEM_SERVICE_PORT must be set"));
-    let addr = format!("0.0.0.0:{}", port).parse()?;
+    // NOTE: HTTP と gRPC の両方のサーバーに終了を伝えるため watch を使う
+    let (signal_tx, signal_rx) = tokio::sync::watch::channel(());
+    tokio::spawn(async move {
+        shutdown_signal(readiness).await;
+        let _ = signal_tx.send(());
+    });
 
+    tokio::spawn(
+        event::FileConsumer::new(&config.event_broker_dir, event::TENANT_EVENTS_TOPIC).run(
+            event::TenantEventHandler::new(
+                datastore.clone(),
+                tenant_client.clone(),
+                quotas.clone(),
+            ),
+            signal_rx.clone(),
+        ),
+    );
+
+    let addr = format!("0.0.0.0:{}", &config.port).parse()?;
     tracing::info!("ItemService listening on: {}", &addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let http_server = axum::Server::bind(&addr)
+        .serve(app.into_make_service_with_connect_info::<observe::middleware::ConnectionInfo>())
+        .with_graceful_shutdown(signal_received(signal_rx.clone()));
+
+    let grpc_addr = format!("0.0.0.0:{}", &config.grpc_port).parse()?;
+    tracing::info!("ItemService gRPC listening on: {}", &grpc_addr);
+    let grpc_server = tonic::transport::Server::builder()
+        .layer(common::observe::grpc_server::trace_layer(span_policy))
+        .layer(tower_http::catch_panic::CatchPanicLayer::new())
+        .add_service(service::reflection::reflection_service()?)
+        .add_service(service::item::item_service(
+            datastore,
+            tenant_client,
+            quotas,
+        ))
+        .serve_with_shutdown(grpc_addr, signal_received(signal_rx.clone()));
+
+    let server = async {
+        tokio::try_join!(
+            async {
+                http_server
+                    .await
+                    .map_err(Box::<dyn std::error::Error>::from)
+            },
+            async {
+                grpc_server
+                    .await
+                    .map_err(Box::<dyn std::error::Error>::from)
+            },
+        )
+        .map(|_| ())
+    };
+    serve_with_deadline(server, signal_rx, config.drain_timeout).await?;
 
-    shutdown_tracer();
+    telemetry.shutdown().await;
 
     Ok(())
 }
 
+async fn panic() {
+    panic!("panic occured")
+}
+
 #[tracing::instrument]
 async fn span() {
-    tracing::event!(observe::LOG_LEVEL, "sleep event");
+    tracing::event!(common::observe::LOG_LEVEL, "sleep event");
     tokio::join!(sleep_500ms(), sleep_1500ms());
 }
 
@@ -59,7 +137,33 @@ async fn sleep_1500ms() {
     tracing::info!("finish");
 }
 
-async fn shutdown_signal() {
+async fn signal_received(mut signal_rx: tokio::sync::watch::Receiver<()>) {
+    let _ = signal_rx.changed().await;
+}
+
+// NOTE: シグナル受信後 drain_timeout を過ぎても接続が残っている場合は、drain を打ち切って終了する
+async fn serve_with_deadline<E>(
+    server: impl std::future::Future<Output = Result<(), E>>,
+    mut signal_rx: tokio::sync::watch::Receiver<()>,
+    drain_timeout: std::time::Duration,
+) -> Result<(), E> {
+    let deadline = async {
+        if signal_rx.changed().await.is_err() {
+            // NOTE: シグナルを受け取らずにサーバーが終了した場合は deadline を設けない
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(drain_timeout).await;
+    };
+    tokio::select! {
+        result = server => result,
+        _ = deadline => {
+            tracing::warn!("drain timed out after {:?}, closing remaining connections", drain_timeout);
+            Ok(())
+        }
+    }
+}
+
+async fn shutdown_signal(readiness: health::Readiness) {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -83,4 +187,9 @@ async fn shutdown_signal() {
     }
 
     tracing::info!("signal received, starting graceful shutdown");
+
+    // NOTE: ロードバランサーが /readyz の失敗を検知してトラフィックを外すまで待ってから
+    // 新規接続の受け付けを止める
+    readiness.shutdown();
+    tokio::time::sleep(READINESS_DRAIN_DELAY).await;
 }
@@ -1,38 +1,109 @@
+use opentelemetry::baggage::BaggageExt as _;
 use opentelemetry::trace::TraceContextExt as _;
 use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 
-use crate::observe::LOG_LEVEL;
+use common::observe::LOG_LEVEL;
 
-pub fn trace_layer() -> tower_http::trace::TraceLayer<
-    tower_http::classify::SharedClassifier<tower_http::classify::ServerErrorsAsFailures>,
-    OpentelemetryMakeSpan,
-    OpentelemetryOnRequest,
-    OpentelemetryOnResponse,
+pub mod grpc_client;
+pub mod metrics;
+
+pub fn trace_layer(
+    policy: common::observe::span_policy::SpanPolicy,
+) -> tower::layer::util::Stack<
+    metrics::HttpServerMetricsLayer,
+    tower_http::trace::TraceLayer<
+        tower_http::classify::SharedClassifier<tower_http::classify::ServerErrorsAsFailures>,
+        OpentelemetryMakeSpan,
+        OpentelemetryOnRequest,
+        OpentelemetryOnResponse,
+    >,
 > {
-    tower_http::trace::TraceLayer::new_for_http()
-        .make_span_with(OpentelemetryMakeSpan)
-        .on_request(OpentelemetryOnRequest)
-        .on_response(OpentelemetryOnResponse)
+    tower::layer::util::Stack::new(
+        metrics::HttpServerMetricsLayer::new(),
+        tower_http::trace::TraceLayer::new_for_http()
+            .make_span_with(OpentelemetryMakeSpan::new(policy))
+            .on_request(OpentelemetryOnRequest)
+            .on_response(OpentelemetryOnResponse),
+    )
+}
+
+// NOTE: ConnectInfo<SocketAddr> では接続先の情報しか取れないため、待ち受け側のアドレスも保持する
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    remote_addr: std::net::SocketAddr,
+    local_addr: std::net::SocketAddr,
+}
+
+impl axum::extract::connect_info::Connected<&hyper::server::conn::AddrStream> for ConnectionInfo {
+    fn connect_info(target: &hyper::server::conn::AddrStream) -> Self {
+        Self {
+            remote_addr: target.remote_addr(),
+            local_addr: target.local_addr(),
+        }
+    }
 }
 
 #[derive(Clone)]
-pub struct OpentelemetryMakeSpan;
+pub struct OpentelemetryMakeSpan {
+    policy: std::sync::Arc<common::observe::span_policy::SpanPolicy>,
+}
+
+impl OpentelemetryMakeSpan {
+    pub fn new(policy: common::observe::span_policy::SpanPolicy) -> Self {
+        Self {
+            policy: std::sync::Arc::new(policy),
+        }
+    }
+}
 
 impl<B> tower_http::trace::MakeSpan<B> for OpentelemetryMakeSpan {
     fn make_span(&mut self, req: &http::Request<B>) -> tracing::Span {
-        let span = tracing::span!(
-            LOG_LEVEL,
-            "",
-            otel.name = %req.uri(),
-            span.kind = "server",
-            http.method = tracing::field::Empty,
-            http.status_code = tracing::field::Empty,
-        );
+        let action = self.policy.action(req.uri().path());
+        if action == common::observe::span_policy::Action::Ignore {
+            return tracing::Span::none();
+        }
+        // NOTE: cardinality を抑えるため URI ではなくマッチしたルートを span 名に使う
+        let name = match req.extensions().get::<axum::extract::MatchedPath>() {
+            Some(route) => format!("{} {}", req.method(), route.as_str()),
+            None => req.method().to_string(),
+        };
+        let span = match action {
+            common::observe::span_policy::Action::Downgrade => tracing::debug_span!(
+                "",
+                otel.name = %name,
+                otel.kind = "server",
+                http.method = tracing::field::Empty,
+                http.route = tracing::field::Empty,
+                http.target = tracing::field::Empty,
+                http.scheme = tracing::field::Empty,
+                http.status_code = tracing::field::Empty,
+                net.peer.ip = tracing::field::Empty,
+                net.peer.port = tracing::field::Empty,
+                net.host.port = tracing::field::Empty,
+                user_agent.original = tracing::field::Empty,
+            ),
+            _ => tracing::span!(
+                LOG_LEVEL,
+                "",
+                otel.name = %name,
+                otel.kind = "server",
+                http.method = tracing::field::Empty,
+                http.route = tracing::field::Empty,
+                http.target = tracing::field::Empty,
+                http.scheme = tracing::field::Empty,
+                http.status_code = tracing::field::Empty,
+                net.peer.ip = tracing::field::Empty,
+                net.peer.port = tracing::field::Empty,
+                net.host.port = tracing::field::Empty,
+                user_agent.original = tracing::field::Empty,
+            ),
+        };
 
         let parent_cx = opentelemetry::global::get_text_map_propagator(|p| {
             p.extract(&opentelemetry_http::HeaderExtractor(req.headers()))
         });
-        if parent_cx.span().span_context().is_valid() {
+        // NOTE: trace context がなくても baggage だけ伝播される場合がある
+        if parent_cx.span().span_context().is_valid() || !parent_cx.baggage().is_empty() {
             span.set_parent(parent_cx);
         }
 
@@ -47,8 +118,49 @@ impl<B> tower_http::trace::OnRequest<B> for OpentelemetryOnRequest {
     fn on_request(&mut self, req: &http::Request<B>, span: &tracing::Span) {
         span.record(
             opentelemetry_semantic_conventions::trace::HTTP_METHOD.as_str(),
-            &tracing::field::display(req.method()),
+            tracing::field::display(req.method()),
+        );
+        if let Some(route) = req.extensions().get::<axum::extract::MatchedPath>() {
+            span.record(
+                opentelemetry_semantic_conventions::trace::HTTP_ROUTE.as_str(),
+                route.as_str(),
+            );
+        }
+        if let Some(target) = req.uri().path_and_query() {
+            span.record(
+                opentelemetry_semantic_conventions::trace::HTTP_TARGET.as_str(),
+                target.as_str(),
+            );
+        }
+        span.record(
+            opentelemetry_semantic_conventions::trace::HTTP_SCHEME.as_str(),
+            req.uri().scheme_str().unwrap_or("http"),
         );
+        if let Some(axum::extract::ConnectInfo(info)) = req
+            .extensions()
+            .get::<axum::extract::ConnectInfo<ConnectionInfo>>()
+        {
+            // NOTE: semantic conventions 0.11 で net.peer.ip の定数がなくなったため、gRPC と同じ名前を直接指定する
+            span.record(
+                "net.peer.ip",
+                tracing::field::display(info.remote_addr.ip()),
+            );
+            span.record(
+                opentelemetry_semantic_conventions::trace::NET_PEER_PORT.as_str(),
+                info.remote_addr.port(),
+            );
+            span.record(
+                opentelemetry_semantic_conventions::trace::NET_HOST_PORT.as_str(),
+                info.local_addr.port(),
+            );
+        }
+        if let Some(user_agent) = req
+            .headers()
+            .get(http::header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+        {
+            span.record("user_agent.original", user_agent);
+        }
     }
 }
 
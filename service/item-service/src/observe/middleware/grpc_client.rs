@@ -0,0 +1,317 @@
+use opentelemetry::metrics::{Histogram, Unit};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use common::observe::LOG_LEVEL;
+
+// read more: https://opentelemetry.io/docs/specs/otel/metrics/semantic_conventions/rpc-metrics/
+#[derive(Clone)]
+pub struct RpcClientMetrics {
+    duration: Histogram<f64>,
+}
+
+impl RpcClientMetrics {
+    pub fn new() -> Self {
+        let meter = opentelemetry::global::meter(env!("CARGO_PKG_NAME"));
+        Self {
+            duration: meter
+                .f64_histogram("rpc.client.duration")
+                .with_description("Measures the duration of outbound RPC.")
+                .with_unit(Unit::new("ms"))
+                .init(),
+        }
+    }
+}
+
+// NOTE: tonic の Channel をラップして使う
+// e.g. `tower::ServiceBuilder::new().layer(GrpcClientLayer::new(&endpoint)).service(channel)`
+#[derive(Clone)]
+pub struct GrpcClientLayer {
+    metrics: RpcClientMetrics,
+    peer_name: Option<String>,
+    peer_port: Option<u16>,
+}
+
+impl GrpcClientLayer {
+    // NOTE: Channel に渡る前のリクエストの URI にはパスしか含まれないため、接続先は Endpoint から取得する
+    pub fn new(endpoint: &tonic::transport::Endpoint) -> Self {
+        let uri = endpoint.uri();
+        Self {
+            metrics: RpcClientMetrics::new(),
+            peer_name: uri.host().map(|host| host.to_string()),
+            peer_port: uri.port_u16().or_else(|| match uri.scheme_str() {
+                Some("https") => Some(443),
+                Some("http") => Some(80),
+                _ => None,
+            }),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for GrpcClientLayer {
+    type Service = GrpcClientService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcClientService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcClientService<S> {
+    inner: S,
+    layer: GrpcClientLayer,
+}
+
+impl<S, ReqBody, ResBody> tower::Service<http::Request<ReqBody>> for GrpcClientService<S>
+where
+    S: tower::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Error: std::fmt::Display,
+    ResBody: http_body::Body,
+{
+    type Response = http::Response<ResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let call = Call::start(&self.layer, req.uri().path());
+        opentelemetry::global::get_text_map_propagator(|p| {
+            p.inject_context(
+                &call.span.context(),
+                &mut opentelemetry_http::HeaderInjector(req.headers_mut()),
+            )
+        });
+        let inner = {
+            let _guard = call.span.enter();
+            self.inner.call(req)
+        };
+        ResponseFuture {
+            inner,
+            call: Some(call),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        call: Option<Call>,
+    }
+}
+
+impl<F, ResBody, E> std::future::Future for ResponseFuture<F>
+where
+    F: std::future::Future<Output = Result<http::Response<ResBody>, E>>,
+    E: std::fmt::Display,
+{
+    type Output = Result<http::Response<ResponseBody<ResBody>>, E>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.project();
+        let result = {
+            let _guard = this.call.as_ref().map(|call| call.span.enter());
+            std::task::ready!(this.inner.poll(cx))
+        };
+        let mut call = this.call.take().expect("polled after completion");
+        match result {
+            Ok(res) => {
+                // NOTE: エラー時は trailers-only で返されるため header に grpc-status が含まれる
+                if let Some(status) = tonic::Status::from_header_map(res.headers()) {
+                    call.status = Some(status);
+                }
+                std::task::Poll::Ready(Ok(res.map(|inner| ResponseBody {
+                    inner,
+                    call: Some(call),
+                })))
+            }
+            Err(e) => {
+                call.status = Some(tonic::Status::unavailable(e.to_string()));
+                std::task::Poll::Ready(Err(e))
+            }
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseBody<B> {
+        #[pin]
+        inner: B,
+        call: Option<Call>,
+    }
+}
+
+impl<B> http_body::Body for ResponseBody<B>
+where
+    B: http_body::Body,
+    B::Error: std::fmt::Display,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll_data(cx));
+        if let (Some(call), Some(Err(e))) = (this.call.as_mut(), &result) {
+            call.status = Some(tonic::Status::unknown(e.to_string()));
+        }
+        std::task::Poll::Ready(result)
+    }
+
+    fn poll_trailers(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll_trailers(cx));
+        if let Some(mut call) = this.call.take() {
+            match &result {
+                Ok(Some(trailers)) => {
+                    if let Some(status) = tonic::Status::from_header_map(trailers) {
+                        call.status = Some(status);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => call.status = Some(tonic::Status::unknown(e.to_string())),
+            }
+        }
+        std::task::Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// NOTE: Drop 時に span と計測値を記録する
+// ストリームの途中で呼び出し側がレスポンスを破棄した場合も記録されるようにするため
+struct Call {
+    metrics: RpcClientMetrics,
+    attributes: Vec<opentelemetry::KeyValue>,
+    span: tracing::Span,
+    start: std::time::Instant,
+    status: Option<tonic::Status>,
+}
+
+impl Call {
+    fn start(layer: &GrpcClientLayer, full_method: &str) -> Self {
+        let span = tracing::span!(
+            LOG_LEVEL,
+            "",
+            otel.name = %full_method.trim_start_matches('/'),
+            otel.kind = "client",
+            rpc.system = "grpc",
+            rpc.service = tracing::field::Empty,
+            rpc.method = tracing::field::Empty,
+            net.peer.name = tracing::field::Empty,
+            net.peer.port = tracing::field::Empty,
+            rpc.grpc.status_code = tracing::field::Empty,
+            rpc.grpc.message = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+
+        let mut attributes = vec![opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::trace::RPC_SYSTEM,
+            "grpc",
+        )];
+        let methods: Vec<_> = full_method.split('/').collect();
+        if let (Some(service), Some(method)) = (methods.get(1), methods.get(2)) {
+            span.record(
+                opentelemetry_semantic_conventions::trace::RPC_SERVICE.as_str(),
+                service,
+            );
+            span.record(
+                opentelemetry_semantic_conventions::trace::RPC_METHOD.as_str(),
+                method,
+            );
+            attributes.push(opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::RPC_SERVICE,
+                service.to_string(),
+            ));
+            attributes.push(opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::RPC_METHOD,
+                method.to_string(),
+            ));
+        }
+        if let Some(peer_name) = &layer.peer_name {
+            span.record(
+                opentelemetry_semantic_conventions::trace::NET_PEER_NAME.as_str(),
+                peer_name.as_str(),
+            );
+            attributes.push(opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::NET_PEER_NAME,
+                peer_name.clone(),
+            ));
+        }
+        if let Some(peer_port) = layer.peer_port {
+            span.record(
+                opentelemetry_semantic_conventions::trace::NET_PEER_PORT.as_str(),
+                peer_port,
+            );
+            attributes.push(opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::NET_PEER_PORT,
+                peer_port as i64,
+            ));
+        }
+
+        Self {
+            metrics: layer.metrics.clone(),
+            attributes,
+            span,
+            start: std::time::Instant::now(),
+            status: None,
+        }
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        // NOTE: status を受け取る前に drop された場合は呼び出し側によるキャンセルとみなす
+        let status = self
+            .status
+            .take()
+            .unwrap_or_else(|| tonic::Status::cancelled("response dropped"));
+        let code = status.code();
+
+        self.span.record(
+            opentelemetry_semantic_conventions::trace::RPC_GRPC_STATUS_CODE.as_str(),
+            code as i32,
+        );
+        if !status.message().is_empty() {
+            self.span.record("rpc.grpc.message", status.message());
+        }
+        let status_code = match code {
+            tonic::Code::Ok => "OK",
+            _ => "ERROR",
+        };
+        self.span.record("otel.status_code", status_code);
+
+        let mut attributes = self.attributes.clone();
+        attributes.push(opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::trace::RPC_GRPC_STATUS_CODE,
+            code as i64,
+        ));
+        let latency = self.start.elapsed().as_secs_f64() * 1000.0;
+        self.metrics
+            .duration
+            .record(&self.span.context(), latency, &attributes);
+    }
+}
@@ -0,0 +1,266 @@
+use opentelemetry::metrics::{Histogram, Unit, UpDownCounter};
+
+// read more: https://opentelemetry.io/docs/specs/otel/metrics/semantic_conventions/http-metrics/
+#[derive(Clone)]
+pub struct HttpServerMetrics {
+    duration: Histogram<f64>,
+    request_size: Histogram<u64>,
+    response_size: Histogram<u64>,
+    active_requests: UpDownCounter<i64>,
+}
+
+impl HttpServerMetrics {
+    pub fn new() -> Self {
+        let meter = opentelemetry::global::meter(env!("CARGO_PKG_NAME"));
+        Self {
+            duration: meter
+                .f64_histogram("http.server.duration")
+                .with_description("Measures the duration of inbound HTTP requests.")
+                .with_unit(Unit::new("ms"))
+                .init(),
+            request_size: meter
+                .u64_histogram("http.server.request.size")
+                .with_description("Measures the size of HTTP request messages.")
+                .with_unit(Unit::new("By"))
+                .init(),
+            response_size: meter
+                .u64_histogram("http.server.response.size")
+                .with_description("Measures the size of HTTP response messages.")
+                .with_unit(Unit::new("By"))
+                .init(),
+            active_requests: meter
+                .i64_up_down_counter("http.server.active_requests")
+                .with_description(
+                    "Measures the number of concurrent HTTP requests that are currently in-flight.",
+                )
+                .init(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpServerMetricsLayer {
+    metrics: HttpServerMetrics,
+}
+
+impl HttpServerMetricsLayer {
+    pub fn new() -> Self {
+        Self {
+            metrics: HttpServerMetrics::new(),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for HttpServerMetricsLayer {
+    type Service = HttpServerMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpServerMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpServerMetricsService<S> {
+    inner: S,
+    metrics: HttpServerMetrics,
+}
+
+impl<S, ReqBody, ResBody> tower::Service<http::Request<ReqBody>> for HttpServerMetricsService<S>
+where
+    S: tower::Service<http::Request<tonic::transport::Body>, Response = http::Response<ResBody>>,
+    ReqBody: http_body::Body + Send + 'static,
+    ReqBody::Data: Into<bytes::Bytes>,
+    ReqBody::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResBody: http_body::Body,
+{
+    type Response = http::Response<ResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let (parts, body) = req.into_parts();
+        let (body, request_size) = common::observe::body::count_read_bytes(body);
+        let req = http::Request::from_parts(parts, body);
+        let call = Call::start(self.metrics.clone(), &req, request_size);
+        ResponseFuture {
+            inner: self.inner.call(req),
+            call: Some(call),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        call: Option<Call>,
+    }
+}
+
+impl<F, ResBody, E> std::future::Future for ResponseFuture<F>
+where
+    F: std::future::Future<Output = Result<http::Response<ResBody>, E>>,
+{
+    type Output = Result<http::Response<ResponseBody<ResBody>>, E>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll(cx));
+        let mut call = this.call.take().expect("polled after completion");
+        match result {
+            Ok(res) => {
+                call.status_code = Some(res.status());
+                std::task::Poll::Ready(Ok(res.map(|inner| ResponseBody {
+                    inner,
+                    call: Some(call),
+                })))
+            }
+            Err(e) => {
+                call.status_code = Some(http::StatusCode::INTERNAL_SERVER_ERROR);
+                std::task::Poll::Ready(Err(e))
+            }
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseBody<B> {
+        #[pin]
+        inner: B,
+        call: Option<Call>,
+    }
+}
+
+impl<B> http_body::Body for ResponseBody<B>
+where
+    B: http_body::Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let result = std::task::ready!(this.inner.poll_data(cx));
+        match &result {
+            Some(Ok(chunk)) => {
+                if let Some(call) = this.call.as_mut() {
+                    call.response_size += bytes::Buf::remaining(chunk) as u64;
+                }
+            }
+            Some(Err(_)) => {}
+            // NOTE: body を最後まで送り終えた時点で計測を終える
+            None => {
+                this.call.take();
+            }
+        }
+        std::task::Poll::Ready(result)
+    }
+
+    fn poll_trailers(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// NOTE: Drop 時に計測値を記録する
+// レスポンスの途中でクライアントが切断した場合も in-flight 数が戻るようにするため
+struct Call {
+    metrics: HttpServerMetrics,
+    active_attributes: Vec<opentelemetry::KeyValue>,
+    attributes: Vec<opentelemetry::KeyValue>,
+    start: std::time::Instant,
+    status_code: Option<http::StatusCode>,
+    request_size: common::observe::body::ReadBytes,
+    response_size: u64,
+}
+
+impl Call {
+    fn start<B>(
+        metrics: HttpServerMetrics,
+        req: &http::Request<B>,
+        request_size: common::observe::body::ReadBytes,
+    ) -> Self {
+        let cx = opentelemetry::Context::current();
+        let active_attributes = vec![
+            opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::HTTP_METHOD,
+                req.method().to_string(),
+            ),
+            opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::HTTP_SCHEME,
+                req.uri().scheme_str().unwrap_or("http").to_string(),
+            ),
+        ];
+        metrics.active_requests.add(&cx, 1, &active_attributes);
+
+        let mut attributes = active_attributes.clone();
+        // NOTE: cardinality を抑えるため URI ではなくマッチしたルートを使う
+        if let Some(route) = req.extensions().get::<axum::extract::MatchedPath>() {
+            attributes.push(opentelemetry::KeyValue::new(
+                opentelemetry_semantic_conventions::trace::HTTP_ROUTE,
+                route.as_str().to_string(),
+            ));
+        }
+        Self {
+            metrics,
+            active_attributes,
+            attributes,
+            start: std::time::Instant::now(),
+            status_code: None,
+            request_size,
+            response_size: 0,
+        }
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let cx = &opentelemetry::Context::current();
+        self.metrics
+            .active_requests
+            .add(cx, -1, &self.active_attributes);
+
+        let Some(status_code) = self.status_code else {
+            return;
+        };
+        let mut attributes = self.attributes.clone();
+        attributes.push(opentelemetry::KeyValue::new(
+            opentelemetry_semantic_conventions::trace::HTTP_STATUS_CODE,
+            status_code.as_u16() as i64,
+        ));
+        let latency = self.start.elapsed().as_secs_f64() * 1000.0;
+        self.metrics.duration.record(cx, latency, &attributes);
+        self.metrics
+            .request_size
+            .record(cx, self.request_size.get(), &attributes);
+        self.metrics
+            .response_size
+            .record(cx, self.response_size, &attributes);
+    }
+}
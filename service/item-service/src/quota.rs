@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tonic_types::StatusExt as _;
+
+const RATE_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+// NOTE: tenant ごとの使用量を保持し、tenant-service で設定された quota を適用する
+// 使用量は metrics の callback から同期的に読むため std の Mutex で保持する
+#[derive(Debug, Clone)]
+pub struct QuotaEnforcer {
+    usage: Arc<Mutex<HashMap<ulid::Ulid, TenantUsage>>>,
+}
+
+#[derive(Debug)]
+struct TenantUsage {
+    quota: proto::tenant::v1::Quota,
+    items: u64,
+    window_started_at: std::time::Instant,
+    requests: u32,
+}
+
+impl TenantUsage {
+    fn new(quota: proto::tenant::v1::Quota) -> Self {
+        Self {
+            quota,
+            items: 0,
+            window_started_at: std::time::Instant::now(),
+            requests: 0,
+        }
+    }
+}
+
+impl QuotaEnforcer {
+    pub fn new() -> Self {
+        let enforcer = Self {
+            usage: Arc::new(Mutex::new(HashMap::new())),
+        };
+        enforcer.register_metrics();
+        enforcer
+    }
+
+    // NOTE: 1 分ごとの固定ウィンドウでリクエスト数を数える
+    pub fn check_rate(
+        &self,
+        tenant_id: ulid::Ulid,
+        quota: &proto::tenant::v1::Quota,
+    ) -> Result<(), QuotaExceeded> {
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage
+            .entry(tenant_id)
+            .or_insert_with(|| TenantUsage::new(quota.clone()));
+        usage.quota = quota.clone();
+        if usage.window_started_at.elapsed() >= RATE_WINDOW {
+            usage.window_started_at = std::time::Instant::now();
+            usage.requests = 0;
+        }
+        if quota.requests_per_minute > 0 && usage.requests >= quota.requests_per_minute {
+            return Err(QuotaExceeded {
+                tenant_id,
+                description: format!(
+                    "request rate limit of {} per minute exceeded",
+                    quota.requests_per_minute
+                ),
+            });
+        }
+        usage.requests += 1;
+        Ok(())
+    }
+
+    pub fn record_items(&self, tenant_id: ulid::Ulid, items: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(usage) = usage.get_mut(&tenant_id) {
+            usage.items = items;
+        }
+    }
+
+    pub fn forget(&self, tenant_id: ulid::Ulid) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.remove(&tenant_id);
+    }
+
+    fn register_metrics(&self) {
+        let meter = opentelemetry::global::meter(env!("CARGO_PKG_NAME"));
+        let items_usage = meter
+            .u64_observable_gauge("item.quota.items.usage")
+            .with_description("Number of items owned by the tenant.")
+            .init();
+        let items_limit = meter
+            .u64_observable_gauge("item.quota.items.limit")
+            .with_description("Maximum number of items the tenant may own.")
+            .init();
+        let requests_usage = meter
+            .u64_observable_gauge("item.quota.requests.usage")
+            .with_description("Number of requests made by the tenant in the current minute.")
+            .init();
+        let requests_limit = meter
+            .u64_observable_gauge("item.quota.requests.limit")
+            .with_description("Maximum number of requests the tenant may make per minute.")
+            .init();
+
+        let usage = self.usage.clone();
+        let result = meter.register_callback(move |cx| {
+            let usage = usage.lock().unwrap();
+            for (tenant_id, usage) in usage.iter() {
+                let attributes = [opentelemetry::KeyValue::new(
+                    common::observe::baggage::TENANT_ID_KEY,
+                    tenant_id.to_string(),
+                )];
+                items_usage.observe(cx, usage.items, &attributes);
+                requests_usage.observe(cx, usage.requests.into(), &attributes);
+                // NOTE: 無制限の場合は limit を出さない
+                if usage.quota.max_items > 0 {
+                    items_limit.observe(cx, usage.quota.max_items, &attributes);
+                }
+                if usage.quota.requests_per_minute > 0 {
+                    requests_limit.observe(cx, usage.quota.requests_per_minute.into(), &attributes);
+                }
+            }
+        });
+        if let Err(e) = result {
+            tracing::error!("failed to register quota metrics: {}", e);
+        }
+    }
+}
+
+// NOTE: tonic::Status は大きいため、Status への変換は呼び出し側の ? で行う
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub tenant_id: ulid::Ulid,
+    pub description: String,
+}
+
+impl From<QuotaExceeded> for tonic::Status {
+    fn from(e: QuotaExceeded) -> Self {
+        tonic::Status::with_error_details(
+            tonic::Code::ResourceExhausted,
+            e.description.clone(),
+            tonic_types::ErrorDetails::with_quota_failure_violation(
+                format!("tenant:{}", e.tenant_id),
+                e.description,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic_types::StatusExt as _;
+
+    fn quota(requests_per_minute: u32) -> proto::tenant::v1::Quota {
+        proto::tenant::v1::Quota {
+            requests_per_minute,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn requests_over_the_limit_are_rejected() {
+        let enforcer = super::QuotaEnforcer::new();
+        let tenant_id = ulid::Ulid::new();
+        assert!(enforcer.check_rate(tenant_id, &quota(2)).is_ok());
+        assert!(enforcer.check_rate(tenant_id, &quota(2)).is_ok());
+        let e = enforcer.check_rate(tenant_id, &quota(2)).unwrap_err();
+        assert_eq!(e.tenant_id, tenant_id);
+        assert_eq!(e.description, "request rate limit of 2 per minute exceeded");
+
+        // NOTE: 他の tenant の使用量には影響しない
+        assert!(enforcer.check_rate(ulid::Ulid::new(), &quota(2)).is_ok());
+    }
+
+    #[test]
+    fn zero_means_unlimited() {
+        let enforcer = super::QuotaEnforcer::new();
+        let tenant_id = ulid::Ulid::new();
+        for _ in 0..1000 {
+            assert!(enforcer.check_rate(tenant_id, &quota(0)).is_ok());
+        }
+    }
+
+    #[test]
+    fn requests_are_counted_again_in_the_next_window() {
+        let enforcer = super::QuotaEnforcer::new();
+        let tenant_id = ulid::Ulid::new();
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_ok());
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_err());
+
+        enforcer
+            .usage
+            .lock()
+            .unwrap()
+            .get_mut(&tenant_id)
+            .unwrap()
+            .window_started_at -= super::RATE_WINDOW;
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_ok());
+    }
+
+    #[test]
+    fn updated_quota_applies_to_the_current_window() {
+        let enforcer = super::QuotaEnforcer::new();
+        let tenant_id = ulid::Ulid::new();
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_ok());
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_err());
+        assert!(enforcer.check_rate(tenant_id, &quota(2)).is_ok());
+    }
+
+    #[test]
+    fn forgotten_tenant_starts_over() {
+        let enforcer = super::QuotaEnforcer::new();
+        let tenant_id = ulid::Ulid::new();
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_ok());
+        enforcer.forget(tenant_id);
+        assert!(enforcer.check_rate(tenant_id, &quota(1)).is_ok());
+    }
+
+    #[test]
+    fn quota_exceeded_is_resource_exhausted_with_quota_failure() {
+        let tenant_id = ulid::Ulid::new();
+        let status = tonic::Status::from(super::QuotaExceeded {
+            tenant_id,
+            description: "item limit of 10 exceeded".to_string(),
+        });
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.message(), "item limit of 10 exceeded");
+        let violations = status.get_details_quota_failure().unwrap().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].subject, format!("tenant:{}", tenant_id));
+        assert_eq!(violations[0].description, "item limit of 10 exceeded");
+    }
+}
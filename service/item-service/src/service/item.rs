@@ -0,0 +1,224 @@
+use tracing::Instrument as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+pub mod model;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+pub fn item_service(
+    datastore: crate::datastore::InMemory,
+    tenant_client: crate::client::TenantClient,
+    quotas: crate::quota::QuotaEnforcer,
+) -> proto::item::v1::item_service_server::ItemServiceServer<ItemService> {
+    proto::item::v1::item_service_server::ItemServiceServer::new(ItemService::new(
+        datastore,
+        tenant_client,
+        quotas,
+    ))
+}
+
+#[derive(Debug)]
+pub struct ItemService {
+    datastore: crate::datastore::InMemory,
+    tenant_client: crate::client::TenantClient,
+    quotas: crate::quota::QuotaEnforcer,
+}
+
+impl ItemService {
+    pub fn new(
+        datastore: crate::datastore::InMemory,
+        tenant_client: crate::client::TenantClient,
+        quotas: crate::quota::QuotaEnforcer,
+    ) -> Self {
+        Self {
+            datastore,
+            tenant_client,
+            quotas,
+        }
+    }
+
+    // NOTE: リクエスト数の制限は tenant を指定するリクエスト (作成と tenant を絞った一覧) にだけ適用する
+    async fn admit(
+        &self,
+        tenant_id: ulid::Ulid,
+    ) -> Result<proto::tenant::v1::Quota, tonic::Status> {
+        // NOTE: tenant-service への問い合わせにも tenant.id を伝播させる
+        let span = tracing::info_span!("resolve_tenant");
+        span.set_parent(common::observe::baggage::with_tenant_id(tenant_id));
+        let quota = self
+            .tenant_client
+            .tenant_quota(tenant_id)
+            .instrument(span)
+            .await?;
+        self.quotas.check_rate(tenant_id, &quota)?;
+        Ok(quota)
+    }
+}
+
+#[tonic::async_trait]
+impl proto::item::v1::item_service_server::ItemService for ItemService {
+    #[tracing::instrument]
+    async fn create_item(
+        &self,
+        req: tonic::Request<proto::item::v1::CreateItemRequest>,
+    ) -> Result<tonic::Response<proto::item::v1::CreateItemResponse>, tonic::Status> {
+        let req = req.into_inner();
+        let tenant_id = parse_ulid("tenant_id", req.tenant_id)?;
+        if req.name.is_empty() {
+            return Err(tonic::Status::invalid_argument("name must not be empty"));
+        }
+        let quota = self.admit(tenant_id).await?;
+
+        let item = model::Item::new(tenant_id, req.name, req.description, req.price);
+        let id = item.id;
+        let count = self
+            .datastore
+            .insert_item(id, item, quota.max_items)
+            .await
+            .map_err(|count| {
+                self.quotas.record_items(tenant_id, count);
+                crate::quota::QuotaExceeded {
+                    tenant_id,
+                    description: format!("item limit of {} exceeded", quota.max_items),
+                }
+            })?;
+        self.quotas.record_items(tenant_id, count);
+        let res = proto::item::v1::CreateItemResponse {
+            id: Some(proto::lib::v1::Ulid {
+                value: id.to_string(),
+            }),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn get_item(
+        &self,
+        req: tonic::Request<proto::item::v1::GetItemRequest>,
+    ) -> Result<tonic::Response<proto::item::v1::GetItemResponse>, tonic::Status> {
+        let id = parse_ulid("id", req.into_inner().id)?;
+        let item = self
+            .datastore
+            .get_item(id)
+            .await
+            .ok_or_else(|| not_found(id))?;
+        let res = proto::item::v1::GetItemResponse {
+            item: Some(item.into()),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn list_items(
+        &self,
+        req: tonic::Request<proto::item::v1::ListItemsRequest>,
+    ) -> Result<tonic::Response<proto::item::v1::ListItemsResponse>, tonic::Status> {
+        let req = req.into_inner();
+        let tenant_id = req
+            .tenant_id
+            .map(|id| parse_ulid("tenant_id", Some(id)))
+            .transpose()?;
+        if let Some(tenant_id) = tenant_id {
+            self.admit(tenant_id).await?;
+        }
+        // NOTE: page_token には前のページの最後の ID を使う
+        let after = req
+            .page_token
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse()
+                    .map_err(|_| InvalidArgument("invalid page_token".to_string()))
+            })
+            .transpose()?;
+        let page_size = req
+            .page_size
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .min(MAX_PAGE_SIZE) as usize;
+
+        let items = self.datastore.list_items(tenant_id, after, page_size).await;
+        let next_page_token = match items.last() {
+            Some(item) if items.len() == page_size => item.id.to_string(),
+            _ => String::new(),
+        };
+        let res = proto::item::v1::ListItemsResponse {
+            items: items.into_iter().map(|i| i.into()).collect(),
+            next_page_token,
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn update_item(
+        &self,
+        req: tonic::Request<proto::item::v1::UpdateItemRequest>,
+    ) -> Result<tonic::Response<proto::item::v1::UpdateItemResponse>, tonic::Status> {
+        let req = req.into_inner();
+        let id = parse_ulid("id", req.id)?;
+        if req.name.as_deref() == Some("") {
+            return Err(tonic::Status::invalid_argument("name must not be empty"));
+        }
+
+        let item = self
+            .datastore
+            .update_item(id, |item| {
+                if let Some(name) = req.name {
+                    item.name = name;
+                }
+                if let Some(description) = req.description {
+                    item.description = description;
+                }
+                if let Some(price) = req.price {
+                    item.price = price;
+                }
+            })
+            .await
+            .ok_or_else(|| not_found(id))?;
+        let res = proto::item::v1::UpdateItemResponse {
+            item: Some(item.into()),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    #[tracing::instrument]
+    async fn delete_item(
+        &self,
+        req: tonic::Request<proto::item::v1::DeleteItemRequest>,
+    ) -> Result<tonic::Response<proto::item::v1::DeleteItemResponse>, tonic::Status> {
+        let id = parse_ulid("id", req.into_inner().id)?;
+        let item = self
+            .datastore
+            .delete_item(id)
+            .await
+            .ok_or_else(|| not_found(id))?;
+        let count = self.datastore.count_items(item.tenant_id).await;
+        self.quotas.record_items(item.tenant_id, count);
+        Ok(tonic::Response::new(proto::item::v1::DeleteItemResponse {}))
+    }
+}
+
+fn parse_ulid(
+    field: &str,
+    id: Option<proto::lib::v1::Ulid>,
+) -> Result<ulid::Ulid, InvalidArgument> {
+    let id = id.ok_or_else(|| InvalidArgument(format!("{} is required", field)))?;
+    id.value
+        .parse()
+        .map_err(|_| InvalidArgument(format!("invalid {}: {}", field, id.value)))
+}
+
+// NOTE: tonic::Status は大きいため、Status への変換は呼び出し側の ? で行う
+#[derive(Debug)]
+struct InvalidArgument(String);
+
+impl From<InvalidArgument> for tonic::Status {
+    fn from(e: InvalidArgument) -> Self {
+        tonic::Status::invalid_argument(e.0)
+    }
+}
+
+fn not_found(id: ulid::Ulid) -> tonic::Status {
+    tonic::Status::not_found(format!("item not found: {}", id))
+}
@@ -0,0 +1,40 @@
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub id: ulid::Ulid,
+    pub tenant_id: ulid::Ulid,
+    pub name: String,
+    pub description: String,
+    pub price: u64,
+    pub archived: bool,
+}
+
+impl Item {
+    pub fn new(tenant_id: ulid::Ulid, name: String, description: String, price: u64) -> Self {
+        let id = ulid::Ulid::new();
+        Self {
+            id,
+            tenant_id,
+            name,
+            description,
+            price,
+            archived: false,
+        }
+    }
+}
+
+impl From<Item> for proto::item::v1::Item {
+    fn from(item: Item) -> Self {
+        proto::item::v1::Item {
+            id: Some(proto::lib::v1::Ulid {
+                value: item.id.to_string(),
+            }),
+            tenant_id: Some(proto::lib::v1::Ulid {
+                value: item.tenant_id.to_string(),
+            }),
+            name: item.name,
+            description: item.description,
+            price: item.price,
+            archived: item.archived,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+pub fn reflection_service() -> Result<
+    tonic_reflection::server::ServerReflectionServer<
+        impl tonic_reflection::server::ServerReflection,
+    >,
+    tonic_reflection::server::Error,
+> {
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(proto::item::v1::ITEM_SERVICE_FILE_DESCRIPTOR_SET)
+        .build()
+}
@@ -0,0 +1,150 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// NOTE: 複数の address validator にラウンドロビンで振り分ける
+// ヘルスチェックに失敗した endpoint は、次のチェックで復帰するまで選ばない
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    endpoints: Arc<[Endpoint]>,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    base_url: reqwest::Url,
+    healthy: AtomicBool,
+}
+
+impl Endpoints {
+    pub fn new(urls: &[reqwest::Url]) -> Self {
+        Self {
+            endpoints: urls
+                .iter()
+                .map(|url| Endpoint {
+                    base_url: url.clone(),
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // NOTE: すべて unhealthy の場合は、リクエストを止めないよう全体から選ぶ
+    pub fn pick(&self) -> reqwest::Url {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.endpoints.len();
+        (0..len)
+            .map(|i| &self.endpoints[(start + i) % len])
+            .find(|endpoint| endpoint.healthy.load(Ordering::Relaxed))
+            .unwrap_or(&self.endpoints[start % len])
+            .base_url
+            .clone()
+    }
+
+    pub fn base_urls(&self) -> impl Iterator<Item = &reqwest::Url> {
+        self.endpoints.iter().map(|endpoint| &endpoint.base_url)
+    }
+
+    pub fn set_healthy(&self, base_url: &reqwest::Url, healthy: bool) {
+        let Some(endpoint) = self
+            .endpoints
+            .iter()
+            .find(|endpoint| &endpoint.base_url == base_url)
+        else {
+            return;
+        };
+        if endpoint.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            tracing::info!(
+                "address validator {} is now {}",
+                base_url,
+                if healthy { "healthy" } else { "unhealthy" }
+            );
+        }
+    }
+}
+
+// NOTE: base path の下にセグメントを追加する (base_url は末尾が / であることを config で保証している)
+pub fn endpoint_url(base_url: &reqwest::Url, segments: &[&str]) -> reqwest::Url {
+    let mut url = base_url.clone();
+    if let Ok(mut path) = url.path_segments_mut() {
+        path.pop_if_empty().extend(segments);
+    }
+    url
+}
+
+pub fn http_client(
+    tls: &crate::config::ClientTls,
+) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(ca_cert) = &tls.ca_cert {
+        let pem = std::fs::read(ca_cert)
+            .map_err(|e| format!("failed to read {}: {}", ca_cert.display(), e))?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    if let (Some(client_cert), Some(client_key)) = (&tls.client_cert, &tls.client_key) {
+        let cert = std::fs::read(client_cert)
+            .map_err(|e| format!("failed to read {}: {}", client_cert.display(), e))?;
+        let key = std::fs::read(client_key)
+            .map_err(|e| format!("failed to read {}: {}", client_key.display(), e))?;
+        builder = builder.identity(reqwest::Identity::from_pkcs8_pem(&cert, &key)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    fn endpoints() -> (super::Endpoints, Vec<reqwest::Url>) {
+        let urls: Vec<reqwest::Url> = ["http://a/", "http://b/", "http://c/"]
+            .iter()
+            .map(|url| url.parse().unwrap())
+            .collect();
+        (super::Endpoints::new(&urls), urls)
+    }
+
+    fn pick(endpoints: &super::Endpoints, n: usize) -> Vec<String> {
+        (0..n).map(|_| endpoints.pick().to_string()).collect()
+    }
+
+    #[test]
+    fn pick_round_robins_over_healthy_endpoints() {
+        let (endpoints, _) = endpoints();
+        assert_eq!(
+            pick(&endpoints, 4),
+            ["http://a/", "http://b/", "http://c/", "http://a/"]
+        );
+    }
+
+    #[test]
+    fn pick_skips_unhealthy_endpoints() {
+        let (endpoints, urls) = endpoints();
+        endpoints.set_healthy(&urls[1], false);
+        assert_eq!(
+            pick(&endpoints, 4),
+            ["http://a/", "http://c/", "http://c/", "http://a/"]
+        );
+
+        endpoints.set_healthy(&urls[1], true);
+        assert_eq!(pick(&endpoints, 3), ["http://b/", "http://c/", "http://a/"]);
+    }
+
+    #[test]
+    fn pick_falls_back_when_every_endpoint_is_unhealthy() {
+        let (endpoints, urls) = endpoints();
+        for url in &urls {
+            endpoints.set_healthy(url, false);
+        }
+        assert_eq!(
+            pick(&endpoints, 4),
+            ["http://a/", "http://b/", "http://c/", "http://a/"]
+        );
+    }
+
+    #[test]
+    fn endpoint_url_appends_segments_under_the_base_path() {
+        let base_url: reqwest::Url = "http://example.com/validator/".parse().unwrap();
+        assert_eq!(
+            super::endpoint_url(&base_url, &["address", "1 Main St/2F"]).as_str(),
+            "http://example.com/validator/address/1%20Main%20St%2F2F"
+        );
+    }
+}
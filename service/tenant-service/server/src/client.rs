@@ -1,16 +1,52 @@
 use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 
+// NOTE: timeout と rate limit は設定の再読み込みで変わるため、リクエストごとに最新の値を読む
 #[derive(Debug)]
-pub struct Client(reqwest_middleware::ClientWithMiddleware);
+pub struct Client {
+    inner: reqwest_middleware::ClientWithMiddleware,
+    runtime: tokio::sync::watch::Receiver<crate::config::Runtime>,
+    window: std::sync::Mutex<RateWindow>,
+}
+
+#[derive(Debug)]
+struct RateWindow {
+    started_at: std::time::Instant,
+    requests: u32,
+}
 
 impl Client {
-    pub fn new() -> Self {
-        let builder = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+    pub fn new(
+        client: reqwest::Client,
+        runtime: tokio::sync::watch::Receiver<crate::config::Runtime>,
+    ) -> Self {
+        let inner = reqwest_middleware::ClientBuilder::new(client)
             .with(reqwest_tracing::TracingMiddleware::<
                 reqwest_tracing::SpanBackendWithUrl,
             >::new())
             .build();
-        Self(builder)
+        Self {
+            inner,
+            runtime,
+            window: std::sync::Mutex::new(RateWindow {
+                started_at: std::time::Instant::now(),
+                requests: 0,
+            }),
+        }
+    }
+
+    // NOTE: 1 秒ごとの固定ウィンドウで送信数を数え、上限を超えたら false を返す
+    pub fn try_acquire(&self) -> bool {
+        let limit = self.runtime.borrow().address_validator_rate_limit;
+        let mut window = self.window.lock().unwrap();
+        if window.started_at.elapsed() >= std::time::Duration::from_secs(1) {
+            window.started_at = std::time::Instant::now();
+            window.requests = 0;
+        }
+        if limit > 0 && window.requests >= limit {
+            return false;
+        }
+        window.requests += 1;
+        true
     }
 
     pub fn request<U: reqwest::IntoUrl>(
@@ -26,6 +62,10 @@ impl Client {
                 &mut opentelemetry_http::HeaderInjector(&mut headers),
             )
         });
-        self.0.request(method, url).headers(headers)
+        let timeout = self.runtime.borrow().address_validator_timeout;
+        self.inner
+            .request(method, url)
+            .headers(headers)
+            .timeout(timeout)
     }
 }
@@ -1,45 +1,352 @@
-const TENANT_SERVICE_PORT_KEY: &str = "TENANT_SERVICE_PORT";
-const ADDRESS_VALIDATOR_HOST_KEY: &str = "ADDRESS_VALIDATOR_HOST";
-const ADDRESS_VALIDATOR_PORT_KEY: &str = "ADDRESS_VALIDATOR_PORT";
-const OPEN_TELEMETRY_SCHEMA_URL_KEY: &str = "OPEN_TELEMETRY_SCHEMA_URL";
-const OPEN_TELEMETRY_ENDPOINT_KEY: &str = "OPEN_TELEMETRY_ENDPOINT";
+use common::config::reload::Reloadable as _;
+use common::config::source;
+
+pub use common::config::ConfigError;
+
+const KEYS: &[source::Key] = &[
+    source::Key {
+        name: "port",
+        env: &["TENANT_SERVICE_PORT"],
+        default: None,
+    },
+    // NOTE: cert と key を指定すると TLS で待ち受け、client_ca も指定するとクライアント証明書を要求する
+    source::Key {
+        name: "tls.cert",
+        env: &["TENANT_SERVICE_TLS_CERT"],
+        default: None,
+    },
+    source::Key {
+        name: "tls.key",
+        env: &["TENANT_SERVICE_TLS_KEY"],
+        default: None,
+    },
+    source::Key {
+        name: "tls.client_ca",
+        env: &["TENANT_SERVICE_TLS_CLIENT_CA"],
+        default: None,
+    },
+    // NOTE: カンマ区切りで複数の endpoint を指定できる
+    source::Key {
+        name: "address_validator.url",
+        env: &["ADDRESS_VALIDATOR_URL"],
+        default: None,
+    },
+    source::Key {
+        name: "address_validator.tls.ca_cert",
+        env: &["ADDRESS_VALIDATOR_TLS_CA_CERT"],
+        default: None,
+    },
+    source::Key {
+        name: "address_validator.tls.client_cert",
+        env: &["ADDRESS_VALIDATOR_TLS_CLIENT_CERT"],
+        default: None,
+    },
+    source::Key {
+        name: "address_validator.tls.client_key",
+        env: &["ADDRESS_VALIDATOR_TLS_CLIENT_KEY"],
+        default: None,
+    },
+    source::Key {
+        name: "address_validator.timeout_seconds",
+        env: &["ADDRESS_VALIDATOR_TIMEOUT_SECONDS"],
+        default: Some("10"),
+    },
+    // NOTE: 1 秒あたりのリクエスト数で、0 の場合は無制限
+    source::Key {
+        name: "address_validator.rate_limit",
+        env: &["ADDRESS_VALIDATOR_RATE_LIMIT"],
+        default: Some("0"),
+    },
+    source::Key {
+        name: "log.filter",
+        env: &["RUST_LOG"],
+        default: Some("info"),
+    },
+    source::Key {
+        name: "event_broker_dir",
+        env: &["EVENT_BROKER_DIR"],
+        default: None,
+    },
+    source::Key {
+        name: "drain_timeout_seconds",
+        env: &["SHUTDOWN_DRAIN_TIMEOUT_SECONDS"],
+        default: Some("30"),
+    },
+    source::Key {
+        name: "otel.schema_url",
+        env: &["OPEN_TELEMETRY_SCHEMA_URL"],
+        default: None,
+    },
+    // NOTE: 標準の OTEL_EXPORTER_OTLP_ENDPOINT を優先し、なければ独自の変数を使う
+    source::Key {
+        name: "otel.endpoint",
+        env: &["OTEL_EXPORTER_OTLP_ENDPOINT", "OPEN_TELEMETRY_ENDPOINT"],
+        default: None,
+    },
+    source::Key {
+        name: "otel.service_name",
+        env: &["OTEL_SERVICE_NAME"],
+        default: None,
+    },
+    source::Key {
+        name: "otel.deployment_environment",
+        env: &["DEPLOYMENT_ENVIRONMENT"],
+        default: None,
+    },
+    source::Key {
+        name: "otel.propagators",
+        env: &["OTEL_PROPAGATORS"],
+        default: Some("tracecontext,baggage"),
+    },
+    source::Key {
+        name: "otel.span_policy.ignore",
+        env: &["SPAN_POLICY_IGNORE"],
+        default: Some("/grpc.health.v1.Health/*"),
+    },
+    source::Key {
+        name: "otel.span_policy.downgrade",
+        env: &["SPAN_POLICY_DOWNGRADE"],
+        default: Some("/grpc.reflection.v1alpha.ServerReflection/*"),
+    },
+    source::Key {
+        name: "otel.sampling_ratio",
+        env: &["OTEL_TRACES_SAMPLER_ARG"],
+        default: Some("1.0"),
+    },
+    source::Key {
+        name: "otel.shutdown_timeout_seconds",
+        env: &["OTEL_SHUTDOWN_TIMEOUT_SECONDS"],
+        default: Some("5"),
+    },
+];
+
+pub enum Loaded {
+    Run(Box<Config>),
+    // NOTE: --print-config が指定された場合は設定を出力して終了する
+    Printed,
+}
 
 pub struct Config {
-    pub port: u32,
-    pub otel: OpenTelemetry,
-    pub address_validator_host: String,
-    pub address_validator_port: String,
+    pub port: u16,
+    pub tls: Option<ServerTls>,
+    pub otel: common::observe::OpenTelemetry,
+    pub address_validator: AddressValidator,
+    pub drain_timeout: std::time::Duration,
+    pub event_broker_dir: String,
+    pub runtime: Runtime,
+    args: source::Args,
 }
 
 impl Config {
-    pub fn from_env() -> Self {
-        let port = from_env(TENANT_SERVICE_PORT_KEY).parse().unwrap();
-        let address_validator_host = from_env(ADDRESS_VALIDATOR_HOST_KEY);
-        let address_validator_port = from_env(ADDRESS_VALIDATOR_PORT_KEY);
-        let otel = OpenTelemetry::from_env();
-        Self {
-            port,
-            otel,
-            address_validator_host,
-            address_validator_port,
+    // NOTE: 既定値、設定ファイル (--config)、環境変数、CLI の順に読み込む
+    pub fn load(args: impl Iterator<Item = String>) -> Result<Loaded, ConfigError> {
+        let args = source::Args::parse(args)?;
+        let mut sources = source::Sources::load(KEYS, &args);
+        let config = Self {
+            port: sources.required("port"),
+            tls: ServerTls::load(&mut sources),
+            otel: common::observe::OpenTelemetry::load(&mut sources),
+            address_validator: AddressValidator::load(&mut sources),
+            drain_timeout: sources.seconds("drain_timeout_seconds"),
+            event_broker_dir: sources.required("event_broker_dir"),
+            runtime: Runtime::load(&mut sources),
+            args,
+        };
+        // NOTE: 不正な設定を出力しないよう、検証してから出力する
+        let rendered = sources.render();
+        sources.finish()?;
+        if config.args.print_config {
+            print!("{}", rendered);
+            return Ok(Loaded::Printed);
         }
+        Ok(Loaded::Run(Box::new(config)))
+    }
+
+    // NOTE: 設定ファイルの変更を監視し、再読み込みできる設定の変更を通知する
+    pub fn watch_runtime(&self) -> tokio::sync::watch::Receiver<Runtime> {
+        common::config::reload::spawn(KEYS, &self.args, self.runtime.clone())
     }
 }
 
-pub struct OpenTelemetry {
-    pub schema_url: String,
-    pub endpoint: String,
+// NOTE: 再起動せずに変更できる設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Runtime {
+    pub telemetry: common::observe::Runtime,
+    pub address_validator_timeout: std::time::Duration,
+    pub address_validator_rate_limit: u32,
 }
 
-impl OpenTelemetry {
-    fn from_env() -> Self {
+impl common::config::reload::Reloadable for Runtime {
+    const KEYS: &'static [&'static str] = &[
+        "log.filter",
+        "otel.sampling_ratio",
+        "address_validator.timeout_seconds",
+        "address_validator.rate_limit",
+    ];
+
+    fn load(sources: &mut source::Sources) -> Self {
         Self {
-            schema_url: from_env(OPEN_TELEMETRY_SCHEMA_URL_KEY),
-            endpoint: from_env(OPEN_TELEMETRY_ENDPOINT_KEY),
+            telemetry: common::observe::Runtime::load(sources),
+            address_validator_timeout: sources.seconds("address_validator.timeout_seconds"),
+            address_validator_rate_limit: sources.required("address_validator.rate_limit"),
+        }
+    }
+
+    fn diff(&self, other: &Self) -> Vec<String> {
+        let mut changes = self.telemetry.diff(&other.telemetry);
+        if self.address_validator_timeout != other.address_validator_timeout {
+            changes.push(format!(
+                "address_validator.timeout_seconds: {} -> {}",
+                self.address_validator_timeout.as_secs(),
+                other.address_validator_timeout.as_secs()
+            ));
+        }
+        if self.address_validator_rate_limit != other.address_validator_rate_limit {
+            changes.push(format!(
+                "address_validator.rate_limit: {} -> {}",
+                self.address_validator_rate_limit, other.address_validator_rate_limit
+            ));
+        }
+        changes
+    }
+}
+
+impl AsRef<common::observe::Runtime> for Runtime {
+    fn as_ref(&self) -> &common::observe::Runtime {
+        &self.telemetry
+    }
+}
+
+pub struct ServerTls {
+    pub cert: std::path::PathBuf,
+    pub key: std::path::PathBuf,
+    pub client_ca: Option<std::path::PathBuf>,
+}
+
+impl ServerTls {
+    fn load(sources: &mut source::Sources) -> Option<Self> {
+        let cert: Option<std::path::PathBuf> = sources.optional("tls.cert");
+        let key: Option<std::path::PathBuf> = sources.optional("tls.key");
+        let client_ca: Option<std::path::PathBuf> = sources.optional("tls.client_ca");
+        match (cert, key) {
+            (Some(cert), Some(key)) => Some(Self {
+                cert,
+                key,
+                client_ca,
+            }),
+            (None, None) => {
+                if client_ca.is_some() {
+                    sources.invalid("tls.client_ca", "requires tls.cert and tls.key".to_string());
+                }
+                None
+            }
+            _ => {
+                sources.invalid("tls", "cert and key must be set together".to_string());
+                None
+            }
         }
     }
 }
 
-fn from_env(key: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| panic!("{} must be set", key))
+pub struct AddressValidator {
+    pub urls: Vec<reqwest::Url>,
+    pub tls: ClientTls,
+}
+
+impl AddressValidator {
+    fn load(sources: &mut source::Sources) -> Self {
+        let name = "address_validator.url";
+        let raw: String = sources.required(name);
+        let mut urls = vec![];
+        for url in raw.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+            match parse_base_url(url) {
+                Ok(url) => urls.push(url),
+                Err(e) => sources.invalid(name, format!("{}: {}", url, e)),
+            }
+        }
+        // NOTE: 未設定の場合は required がエラーにしている
+        if urls.is_empty() && !raw.is_empty() {
+            sources.invalid(name, "at least one endpoint is required".to_string());
+        }
+
+        let tls = ClientTls {
+            ca_cert: sources.optional("address_validator.tls.ca_cert"),
+            client_cert: sources.optional("address_validator.tls.client_cert"),
+            client_key: sources.optional("address_validator.tls.client_key"),
+        };
+        if tls.client_cert.is_some() != tls.client_key.is_some() {
+            sources.invalid(
+                "address_validator.tls",
+                "client_cert and client_key must be set together".to_string(),
+            );
+        }
+        Self { urls, tls }
+    }
+}
+
+// NOTE: 相対パスを base path の下に連結できるよう、末尾を / にそろえる
+fn parse_base_url(url: &str) -> Result<reqwest::Url, String> {
+    let mut url: reqwest::Url = url.parse().map_err(|e| format!("{}", e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("unsupported scheme: {}", url.scheme()));
+    }
+    if url.host().is_none() {
+        return Err("missing host".to_string());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("query and fragment are not allowed".to_string());
+    }
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+pub struct ClientTls {
+    pub ca_cert: Option<std::path::PathBuf>,
+    pub client_cert: Option<std::path::PathBuf>,
+    pub client_key: Option<std::path::PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn base_url_gets_a_trailing_slash() {
+        assert_eq!(
+            super::parse_base_url("http://address-validator:3000")
+                .unwrap()
+                .as_str(),
+            "http://address-validator:3000/"
+        );
+        assert_eq!(
+            super::parse_base_url("https://example.com/validator")
+                .unwrap()
+                .as_str(),
+            "https://example.com/validator/"
+        );
+        assert_eq!(
+            super::parse_base_url("https://example.com/validator/")
+                .unwrap()
+                .as_str(),
+            "https://example.com/validator/"
+        );
+    }
+
+    #[test]
+    fn invalid_base_urls_are_rejected() {
+        assert!(super::parse_base_url("address-validator:3000").is_err());
+        assert_eq!(
+            super::parse_base_url("ftp://example.com/"),
+            Err("unsupported scheme: ftp".to_string())
+        );
+        assert_eq!(
+            super::parse_base_url("http://example.com/?debug=1"),
+            Err("query and fragment are not allowed".to_string())
+        );
+        assert_eq!(
+            super::parse_base_url("http://example.com/#top"),
+            Err("query and fragment are not allowed".to_string())
+        );
+    }
 }
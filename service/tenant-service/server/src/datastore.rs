@@ -1,15 +1,75 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
 
-#[derive(Debug)]
+// NOTE: tenant とイベントを同じロックの中で書き込み、outbox への書き込みをアトミックにする
+#[derive(Debug, Clone)]
 pub struct InMemory {
-    tenants: Arc<tokio::sync::Mutex<HashMap<ulid::Ulid, crate::service::tenant::model::Tenant>>>,
+    inner: Arc<tokio::sync::Mutex<Inner>>,
+}
+
+// NOTE: watch を再開できるよう、直近の変更を履歴として保持する
+const CHANGE_HISTORY_SIZE: usize = 1024;
+
+#[derive(Debug)]
+struct Inner {
+    tenants: HashMap<ulid::Ulid, crate::service::tenant::model::Tenant>,
+    // NOTE: 同じミリ秒に作られた ULID は発生順に並ばないため、書き込み順の連番を key にする
+    outbox: BTreeMap<u64, OutboxEntry>,
+    outbox_sequence: u64,
+    revision: u64,
+    history: VecDeque<TenantChange>,
+    changes: tokio::sync::broadcast::Sender<TenantChange>,
+}
+
+#[derive(Debug)]
+struct OutboxEntry {
+    event_id: ulid::Ulid,
+    envelope: proto::tenant::v1::TenantEvent,
+    created_at: std::time::Instant,
+    attempts: u32,
+    next_attempt_at: std::time::Instant,
+    delivered_at: Option<std::time::Instant>,
+}
+
+// NOTE: tenant を変更する操作はないため、変更の種類は作成と削除だけ
+#[derive(Debug, Clone, Copy)]
+pub enum ChangeKind {
+    Created,
+    Deleted,
+}
+
+#[derive(Debug, Clone)]
+pub struct TenantChange {
+    pub revision: u64,
+    pub kind: ChangeKind,
+    pub tenant: crate::service::tenant::model::Tenant,
+}
+
+#[derive(Debug)]
+pub enum WatchError {
+    // NOTE: 履歴から消えた revision からは再開できないため、一覧を取り直してもらう
+    Expired,
+    InvalidRevision,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutboxStats {
+    pub pending: u64,
+    pub oldest_pending_age: std::time::Duration,
 }
 
 impl InMemory {
     pub fn new() -> Self {
+        let (changes, _) = tokio::sync::broadcast::channel(CHANGE_HISTORY_SIZE);
         Self {
-            tenants: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            inner: Arc::new(tokio::sync::Mutex::new(Inner {
+                tenants: HashMap::new(),
+                outbox: BTreeMap::new(),
+                outbox_sequence: 0,
+                revision: 0,
+                history: VecDeque::with_capacity(CHANGE_HISTORY_SIZE),
+                changes,
+            })),
         }
     }
 
@@ -18,14 +78,380 @@ impl InMemory {
         &self,
         id: ulid::Ulid,
         tenant: crate::service::tenant::model::Tenant,
+        envelope: proto::tenant::v1::TenantEvent,
     ) {
-        let mut tenants = self.tenants.lock().await;
-        tenants.insert(id, tenant);
+        let mut inner = self.inner.lock().await;
+        inner.tenants.insert(id, tenant.clone());
+        inner.enqueue(envelope);
+        inner.notify(ChangeKind::Created, tenant);
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_tenant(
+        &self,
+        id: ulid::Ulid,
+    ) -> Option<crate::service::tenant::model::Tenant> {
+        let inner = self.inner.lock().await;
+        inner.tenants.get(&id).cloned()
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn list_tenants(&self) -> Vec<crate::service::tenant::model::Tenant> {
-        let tenants = self.tenants.lock().await;
-        tenants.values().cloned().collect()
+        let inner = self.inner.lock().await;
+        inner.tenants.values().cloned().collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_tenant(
+        &self,
+        id: ulid::Ulid,
+        envelope: proto::tenant::v1::TenantEvent,
+    ) -> Option<crate::service::tenant::model::Tenant> {
+        let mut inner = self.inner.lock().await;
+        let tenant = inner.tenants.remove(&id)?;
+        inner.enqueue(envelope);
+        inner.notify(ChangeKind::Deleted, tenant.clone());
+        Some(tenant)
+    }
+
+    // NOTE: 履歴の取得と購読を同じロックの中で行い、その間の変更を取りこぼさないようにする
+    pub async fn watch_tenants(
+        &self,
+        after: Option<u64>,
+    ) -> Result<
+        (
+            Vec<TenantChange>,
+            tokio::sync::broadcast::Receiver<TenantChange>,
+        ),
+        WatchError,
+    > {
+        let inner = self.inner.lock().await;
+        let backlog = match after {
+            None => vec![],
+            Some(after) if after > inner.revision => return Err(WatchError::InvalidRevision),
+            Some(after) => {
+                let oldest = inner
+                    .history
+                    .front()
+                    .map_or(inner.revision + 1, |change| change.revision);
+                if after + 1 < oldest {
+                    return Err(WatchError::Expired);
+                }
+                inner
+                    .history
+                    .iter()
+                    .filter(|change| change.revision > after)
+                    .cloned()
+                    .collect()
+            }
+        };
+        Ok((backlog, inner.changes.subscribe()))
+    }
+
+    // NOTE: 未配信のイベントを発生順に返す
+    // 再送待ちのイベントがあればそこで止め、後続のイベントが追い越さないようにする
+    pub async fn pending_events(
+        &self,
+        limit: usize,
+    ) -> Vec<(ulid::Ulid, proto::tenant::v1::TenantEvent)> {
+        let inner = self.inner.lock().await;
+        let now = std::time::Instant::now();
+        inner
+            .outbox
+            .values()
+            .filter(|entry| entry.delivered_at.is_none())
+            .take_while(|entry| entry.next_attempt_at <= now)
+            .take(limit)
+            .map(|entry| (entry.event_id, entry.envelope.clone()))
+            .collect()
+    }
+
+    pub async fn mark_delivered(&self, id: ulid::Ulid) {
+        let mut inner = self.inner.lock().await;
+        if let Some(entry) = inner.outbox_entry(id) {
+            entry.delivered_at = Some(std::time::Instant::now());
+        }
+    }
+
+    // NOTE: 次の再送までの待ち時間は試行回数をもとに呼び出し側で決める
+    pub async fn mark_failed(
+        &self,
+        id: ulid::Ulid,
+        retry_after: impl FnOnce(u32) -> std::time::Duration,
+    ) {
+        let mut inner = self.inner.lock().await;
+        if let Some(entry) = inner.outbox_entry(id) {
+            entry.attempts += 1;
+            entry.next_attempt_at = std::time::Instant::now() + retry_after(entry.attempts);
+        }
+    }
+
+    pub async fn purge_delivered(&self, retention: std::time::Duration) {
+        let mut inner = self.inner.lock().await;
+        inner.outbox.retain(|_, entry| {
+            entry
+                .delivered_at
+                .is_none_or(|delivered_at| delivered_at.elapsed() < retention)
+        });
+    }
+
+    pub async fn outbox_stats(&self) -> OutboxStats {
+        let inner = self.inner.lock().await;
+        inner
+            .outbox
+            .values()
+            .filter(|entry| entry.delivered_at.is_none())
+            .fold(OutboxStats::default(), |stats, entry| OutboxStats {
+                pending: stats.pending + 1,
+                oldest_pending_age: stats.oldest_pending_age.max(entry.created_at.elapsed()),
+            })
+    }
+
+    // NOTE: インメモリのためロックを取得できるかで疎通を確認する
+    pub async fn ping(&self) {
+        let _inner = self.inner.lock().await;
+    }
+}
+
+impl Inner {
+    fn notify(&mut self, kind: ChangeKind, tenant: crate::service::tenant::model::Tenant) {
+        self.revision += 1;
+        let change = TenantChange {
+            revision: self.revision,
+            kind,
+            tenant,
+        };
+        if self.history.len() == CHANGE_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(change.clone());
+        // NOTE: 購読者がいない場合はエラーになるが、履歴には残っているため無視する
+        let _ = self.changes.send(change);
+    }
+
+    fn enqueue(&mut self, envelope: proto::tenant::v1::TenantEvent) {
+        // NOTE: Ulid::default() は nil なので、ID がない場合は新しく採番する
+        let event_id = match envelope
+            .event_id
+            .as_ref()
+            .and_then(|id| id.value.parse().ok())
+        {
+            Some(id) => id,
+            None => ulid::Ulid::new(),
+        };
+        let now = std::time::Instant::now();
+        self.outbox_sequence += 1;
+        self.outbox.insert(
+            self.outbox_sequence,
+            OutboxEntry {
+                event_id,
+                envelope,
+                created_at: now,
+                attempts: 0,
+                next_attempt_at: now,
+                delivered_at: None,
+            },
+        );
+    }
+
+    // NOTE: outbox は配信済みのものも保持期間の間しか残らないため、線形に探す
+    fn outbox_entry(&mut self, event_id: ulid::Ulid) -> Option<&mut OutboxEntry> {
+        self.outbox
+            .values_mut()
+            .find(|entry| entry.event_id == event_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn tenant(id: ulid::Ulid) -> crate::service::tenant::model::Tenant {
+        let address: crate::service::tenant::model::AddressValidatorResponse =
+            serde_json::from_value(serde_json::json!({ "level": 0, "full": "Tokyo" })).unwrap();
+        crate::service::tenant::model::Tenant::new(
+            id,
+            "tenant".to_string(),
+            address.into(),
+            Default::default(),
+        )
+    }
+
+    fn envelope(event_id: ulid::Ulid) -> proto::tenant::v1::TenantEvent {
+        proto::tenant::v1::TenantEvent {
+            event_id: Some(proto::lib::v1::Ulid {
+                value: event_id.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn pending_ids(events: Vec<(ulid::Ulid, proto::tenant::v1::TenantEvent)>) -> Vec<ulid::Ulid> {
+        events.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[tokio::test]
+    async fn outbox_returns_events_in_order_until_delivered() {
+        let datastore = super::InMemory::new();
+        let (tenant_id, created, deleted) =
+            (ulid::Ulid::new(), ulid::Ulid::new(), ulid::Ulid::new());
+        datastore
+            .insert_tenant(tenant_id, tenant(tenant_id), envelope(created))
+            .await;
+        datastore.delete_tenant(tenant_id, envelope(deleted)).await;
+
+        assert_eq!(
+            pending_ids(datastore.pending_events(10).await),
+            [created, deleted]
+        );
+        assert_eq!(pending_ids(datastore.pending_events(1).await), [created]);
+        assert_eq!(datastore.outbox_stats().await.pending, 2);
+
+        datastore.mark_delivered(created).await;
+        assert_eq!(pending_ids(datastore.pending_events(10).await), [deleted]);
+        assert_eq!(datastore.outbox_stats().await.pending, 1);
+    }
+
+    #[tokio::test]
+    async fn failed_event_waits_for_its_backoff() {
+        let datastore = super::InMemory::new();
+        let (tenant_id, event_id) = (ulid::Ulid::new(), ulid::Ulid::new());
+        datastore
+            .insert_tenant(tenant_id, tenant(tenant_id), envelope(event_id))
+            .await;
+
+        let mut attempts = vec![];
+        datastore
+            .mark_failed(event_id, |n| {
+                attempts.push(n);
+                std::time::Duration::from_secs(60)
+            })
+            .await;
+        assert!(datastore.pending_events(10).await.is_empty());
+        // NOTE: 送信を待っている間も pending として数える
+        assert_eq!(datastore.outbox_stats().await.pending, 1);
+
+        datastore
+            .mark_failed(event_id, |n| {
+                attempts.push(n);
+                std::time::Duration::ZERO
+            })
+            .await;
+        assert_eq!(attempts, [1, 2]);
+        assert_eq!(pending_ids(datastore.pending_events(10).await), [event_id]);
+    }
+
+    #[tokio::test]
+    async fn events_after_a_failed_event_wait_for_it() {
+        let datastore = super::InMemory::new();
+        let (tenant_id, created, deleted) =
+            (ulid::Ulid::new(), ulid::Ulid::new(), ulid::Ulid::new());
+        datastore
+            .insert_tenant(tenant_id, tenant(tenant_id), envelope(created))
+            .await;
+        datastore.delete_tenant(tenant_id, envelope(deleted)).await;
+
+        datastore
+            .mark_failed(created, |_| std::time::Duration::from_secs(60))
+            .await;
+        assert!(datastore.pending_events(10).await.is_empty());
+
+        datastore
+            .mark_failed(created, |_| std::time::Duration::ZERO)
+            .await;
+        assert_eq!(
+            pending_ids(datastore.pending_events(10).await),
+            [created, deleted]
+        );
+    }
+
+    #[tokio::test]
+    async fn delivered_events_are_purged_after_retention() {
+        let datastore = super::InMemory::new();
+        let (tenant_id, event_id) = (ulid::Ulid::new(), ulid::Ulid::new());
+        datastore
+            .insert_tenant(tenant_id, tenant(tenant_id), envelope(event_id))
+            .await;
+        datastore.mark_delivered(event_id).await;
+
+        datastore
+            .purge_delivered(std::time::Duration::from_secs(60))
+            .await;
+        assert_eq!(datastore.inner.lock().await.outbox.len(), 1);
+        datastore.purge_delivered(std::time::Duration::ZERO).await;
+        assert!(datastore.inner.lock().await.outbox.is_empty());
+    }
+
+    fn revisions(changes: &[super::TenantChange]) -> Vec<u64> {
+        changes.iter().map(|change| change.revision).collect()
+    }
+
+    #[tokio::test]
+    async fn watch_without_revision_only_receives_new_changes() {
+        let datastore = super::InMemory::new();
+        let first = ulid::Ulid::new();
+        datastore
+            .insert_tenant(first, tenant(first), envelope(ulid::Ulid::new()))
+            .await;
+
+        let (backlog, mut changes) = datastore.watch_tenants(None).await.unwrap();
+        assert!(backlog.is_empty());
+
+        let second = ulid::Ulid::new();
+        datastore
+            .insert_tenant(second, tenant(second), envelope(ulid::Ulid::new()))
+            .await;
+        datastore
+            .delete_tenant(first, envelope(ulid::Ulid::new()))
+            .await;
+
+        let change = changes.recv().await.unwrap();
+        assert_eq!(change.revision, 2);
+        assert_eq!(change.tenant.id, second);
+        assert!(matches!(change.kind, super::ChangeKind::Created));
+        let change = changes.recv().await.unwrap();
+        assert_eq!(change.revision, 3);
+        assert_eq!(change.tenant.id, first);
+        assert!(matches!(change.kind, super::ChangeKind::Deleted));
+    }
+
+    #[tokio::test]
+    async fn watch_resumes_after_the_given_revision() {
+        let datastore = super::InMemory::new();
+        for _ in 0..3 {
+            let id = ulid::Ulid::new();
+            datastore
+                .insert_tenant(id, tenant(id), envelope(ulid::Ulid::new()))
+                .await;
+        }
+
+        let (backlog, _) = datastore.watch_tenants(Some(1)).await.unwrap();
+        assert_eq!(revisions(&backlog), [2, 3]);
+        let (backlog, _) = datastore.watch_tenants(Some(0)).await.unwrap();
+        assert_eq!(revisions(&backlog), [1, 2, 3]);
+        let (backlog, _) = datastore.watch_tenants(Some(3)).await.unwrap();
+        assert!(backlog.is_empty());
+        assert!(matches!(
+            datastore.watch_tenants(Some(4)).await,
+            Err(super::WatchError::InvalidRevision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn watch_from_a_revision_dropped_from_history_has_expired() {
+        let datastore = super::InMemory::new();
+        let id = ulid::Ulid::new();
+        for _ in 0..super::CHANGE_HISTORY_SIZE + 2 {
+            datastore
+                .insert_tenant(id, tenant(id), envelope(ulid::Ulid::new()))
+                .await;
+        }
+
+        // NOTE: 履歴に残っている最古の revision は 3 なので、2 の次からは再開できる
+        let (backlog, _) = datastore.watch_tenants(Some(2)).await.unwrap();
+        assert_eq!(backlog.len(), super::CHANGE_HISTORY_SIZE);
+        assert_eq!(backlog[0].revision, 3);
+        assert!(matches!(
+            datastore.watch_tenants(Some(1)).await,
+            Err(super::WatchError::Expired)
+        ));
     }
 }
@@ -0,0 +1,99 @@
+use prost::Message as _;
+use tokio::io::AsyncWriteExt as _;
+use tracing::Instrument as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use common::observe::LOG_LEVEL;
+
+pub mod outbox;
+
+pub const TENANT_EVENTS_TOPIC: &str = "tenant-events";
+
+// NOTE: 開発用の broker の代わりとして、イベントを length-delimited な protobuf でファイルに追記する
+// consumer はファイルの末尾を追いかけて読む
+#[derive(Debug, Clone)]
+pub struct FilePublisher {
+    topic: &'static str,
+    path: std::path::PathBuf,
+    lock: std::sync::Arc<tokio::sync::Mutex<()>>,
+}
+
+impl FilePublisher {
+    pub fn new(dir: impl AsRef<std::path::Path>, topic: &'static str) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            topic,
+            path: dir.as_ref().join(format!("{}.bin", topic)),
+            lock: std::sync::Arc::new(tokio::sync::Mutex::new(())),
+        })
+    }
+
+    // NOTE: リクエストの span の context を載せておき、relay が publish するときの親にする
+    pub fn envelope(
+        event: proto::tenant::v1::tenant_event::Event,
+    ) -> proto::tenant::v1::TenantEvent {
+        let mut trace_context = std::collections::HashMap::new();
+        opentelemetry::global::get_text_map_propagator(|p| {
+            p.inject_context(&tracing::Span::current().context(), &mut trace_context)
+        });
+        proto::tenant::v1::TenantEvent {
+            event_id: Some(proto::lib::v1::Ulid {
+                value: ulid::Ulid::new().to_string(),
+            }),
+            occurred_at: Some(std::time::SystemTime::now().into()),
+            trace_context,
+            event: Some(event),
+        }
+    }
+
+    pub async fn publish(
+        &self,
+        mut envelope: proto::tenant::v1::TenantEvent,
+    ) -> std::io::Result<()> {
+        let span = tracing::span!(
+            LOG_LEVEL,
+            "",
+            otel.name = %format!("{} publish", self.topic),
+            otel.kind = "producer",
+            otel.status_code = tracing::field::Empty,
+            messaging.system = "file",
+            messaging.destination.name = self.topic,
+            messaging.operation = "publish",
+            messaging.message.id = tracing::field::Empty,
+            error.message = tracing::field::Empty,
+        );
+        if let Some(event_id) = &envelope.event_id {
+            span.record("messaging.message.id", event_id.value.as_str());
+        }
+        span.set_parent(opentelemetry::global::get_text_map_propagator(|p| {
+            p.extract(&envelope.trace_context)
+        }));
+        // NOTE: consumer からは producer の span を link できるように差し替える
+        envelope.trace_context.clear();
+        opentelemetry::global::get_text_map_propagator(|p| {
+            p.inject_context(&span.context(), &mut envelope.trace_context)
+        });
+
+        let result = self
+            .append(&envelope.encode_length_delimited_to_vec())
+            .instrument(span.clone())
+            .await;
+        if let Err(e) = &result {
+            span.record("otel.status_code", "ERROR");
+            span.record("error.message", e.to_string());
+        }
+        result
+    }
+
+    async fn append(&self, buf: &[u8]) -> std::io::Result<()> {
+        // NOTE: 書き込みが混ざると consumer が読めなくなるため 1 件ずつ書き込む
+        let _guard = self.lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(buf).await?;
+        file.flush().await
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+const BATCH_SIZE: usize = 100;
+const INITIAL_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(60);
+// NOTE: 配信済みのイベントは調査用にしばらく残してから消す
+const DELIVERED_RETENTION: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+// NOTE: datastore の outbox に溜まったイベントを publish し、成功したものを配信済みにする
+// publish 後に配信済みにする前に落ちると再送されるため、at-least-once になる
+// consumer は event_id で重複を除かないため、同じイベントを複数回処理しても結果が変わらないようにする
+pub struct OutboxRelay {
+    datastore: crate::datastore::InMemory,
+    publisher: crate::event::FilePublisher,
+    metrics: OutboxMetrics,
+}
+
+impl OutboxRelay {
+    pub fn new(
+        datastore: crate::datastore::InMemory,
+        publisher: crate::event::FilePublisher,
+    ) -> Self {
+        Self {
+            datastore,
+            publisher,
+            metrics: OutboxMetrics::new(),
+        }
+    }
+
+    pub async fn run(self, mut stop_rx: tokio::sync::oneshot::Receiver<()>) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut stop_rx => break,
+            }
+            self.relay().await;
+        }
+        // NOTE: 停止前に残っているイベントを一度だけ送る
+        self.relay().await;
+    }
+
+    // NOTE: 同じ tenant の TenantCreated より先に TenantDeleted が届かないよう、
+    // 失敗したイベントを飛ばして後続を送ることはせず、次の試行まで待つ
+    async fn relay(&self) {
+        for (id, envelope) in self.datastore.pending_events(BATCH_SIZE).await {
+            if let Err(e) = self.publisher.publish(envelope).await {
+                tracing::warn!("failed to publish tenant event {}: {}", id, e);
+                self.datastore.mark_failed(id, backoff).await;
+                break;
+            }
+            self.datastore.mark_delivered(id).await;
+        }
+        self.datastore.purge_delivered(DELIVERED_RETENTION).await;
+        self.metrics.record(self.datastore.outbox_stats().await);
+    }
+}
+
+fn backoff(attempts: u32) -> std::time::Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+// NOTE: callback は同期的に呼ばれ datastore のロックを取れないため、relay が集計した値を保持しておく
+#[derive(Clone)]
+struct OutboxMetrics {
+    pending: std::sync::Arc<AtomicU64>,
+    lag: std::sync::Arc<AtomicU64>,
+}
+
+impl OutboxMetrics {
+    fn new() -> Self {
+        let metrics = Self {
+            pending: std::sync::Arc::new(AtomicU64::new(0)),
+            lag: std::sync::Arc::new(AtomicU64::new(0f64.to_bits())),
+        };
+
+        let meter = opentelemetry::global::meter(env!("CARGO_PKG_NAME"));
+        let pending = meter
+            .u64_observable_gauge("tenant.outbox.pending")
+            .with_description("Number of tenant events waiting to be published.")
+            .init();
+        let lag = meter
+            .f64_observable_gauge("tenant.outbox.lag")
+            .with_description("Age of the oldest tenant event waiting to be published.")
+            .with_unit(opentelemetry::metrics::Unit::new("s"))
+            .init();
+        let values = metrics.clone();
+        if let Err(e) = meter.register_callback(move |cx| {
+            pending.observe(cx, values.pending.load(Ordering::Relaxed), &[]);
+            lag.observe(cx, f64::from_bits(values.lag.load(Ordering::Relaxed)), &[]);
+        }) {
+            tracing::error!("failed to register outbox metrics: {}", e);
+        }
+        metrics
+    }
+
+    fn record(&self, stats: crate::datastore::OutboxStats) {
+        self.pending.store(stats.pending, Ordering::Relaxed);
+        self.lag.store(
+            stats.oldest_pending_age.as_secs_f64().to_bits(),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message as _;
+
+    fn tenant(id: ulid::Ulid) -> crate::service::tenant::model::Tenant {
+        let address: crate::service::tenant::model::AddressValidatorResponse =
+            serde_json::from_value(serde_json::json!({ "level": 0, "full": "Tokyo" })).unwrap();
+        crate::service::tenant::model::Tenant::new(
+            id,
+            "tenant".to_string(),
+            address.into(),
+            Default::default(),
+        )
+    }
+
+    fn envelope(event: proto::tenant::v1::tenant_event::Event) -> proto::tenant::v1::TenantEvent {
+        crate::event::FilePublisher::envelope(event)
+    }
+
+    fn event_id(envelope: &proto::tenant::v1::TenantEvent) -> ulid::Ulid {
+        envelope.event_id.as_ref().unwrap().value.parse().unwrap()
+    }
+
+    fn published_ids(path: &std::path::Path) -> Vec<ulid::Ulid> {
+        let buf = std::fs::read(path).unwrap_or_default();
+        let mut buf = buf.as_slice();
+        let mut ids = vec![];
+        while !buf.is_empty() {
+            let envelope =
+                proto::tenant::v1::TenantEvent::decode_length_delimited(&mut buf).unwrap();
+            ids.push(event_id(&envelope));
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn failed_event_holds_back_later_events() {
+        let dir = std::env::temp_dir().join(format!("outbox-{}", ulid::Ulid::new()));
+        let publisher =
+            crate::event::FilePublisher::new(&dir, crate::event::TENANT_EVENTS_TOPIC).unwrap();
+        let path = dir.join(format!("{}.bin", crate::event::TENANT_EVENTS_TOPIC));
+        let datastore = crate::datastore::InMemory::new();
+        let relay = super::OutboxRelay::new(datastore.clone(), publisher);
+
+        let (first, second) = (ulid::Ulid::new(), ulid::Ulid::new());
+        let mut ids = vec![];
+        for id in [first, second] {
+            let created = envelope(proto::tenant::v1::tenant_event::Event::Created(
+                proto::tenant::v1::TenantCreated {
+                    tenant_id: Some(proto::lib::v1::Ulid {
+                        value: id.to_string(),
+                    }),
+                    ..Default::default()
+                },
+            ));
+            ids.push(event_id(&created));
+            datastore.insert_tenant(id, tenant(id), created).await;
+        }
+        let deleted = envelope(proto::tenant::v1::tenant_event::Event::Deleted(
+            proto::tenant::v1::TenantDeleted {
+                tenant_id: Some(proto::lib::v1::Ulid {
+                    value: first.to_string(),
+                }),
+            },
+        ));
+        ids.push(event_id(&deleted));
+        datastore.delete_tenant(first, deleted).await;
+
+        // NOTE: 書き込み先をディレクトリにして、最初の publish だけを失敗させる
+        std::fs::create_dir(&path).unwrap();
+        relay.relay().await;
+        std::fs::remove_dir(&path).unwrap();
+
+        // NOTE: 失敗したイベントの backoff 中は、後続のイベントも送らない
+        relay.relay().await;
+        assert!(published_ids(&path).is_empty());
+        assert_eq!(datastore.outbox_stats().await.pending, 3);
+
+        datastore
+            .mark_failed(ids[0], |_| std::time::Duration::ZERO)
+            .await;
+        relay.relay().await;
+        assert_eq!(published_ids(&path), ids);
+        assert_eq!(datastore.outbox_stats().await.pending, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let backoffs: Vec<u64> = [1, 2, 3, 4, 7, 8, 100]
+            .into_iter()
+            .map(|attempts| super::backoff(attempts).as_secs())
+            .collect();
+        assert_eq!(backoffs, [1, 2, 4, 8, 60, 60, 60]);
+    }
+}
@@ -1,39 +1,123 @@
+mod address_validator;
 mod client;
 mod config;
 mod datastore;
-mod observe;
+mod event;
 mod service;
+mod tls;
+
+const READINESS_DRAIN_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = config::Config::from_env();
-    let shutdown_tracer = observe::init(&config.otel.schema_url, &config.otel.endpoint)
+    let config = match config::Config::load(std::env::args().skip(1)) {
+        Ok(config::Loaded::Run(config)) => *config,
+        Ok(config::Loaded::Printed) => return Ok(()),
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let service = common::observe::Service {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+    };
+    let telemetry = common::observe::init(service, &config.otel, &config.runtime.telemetry)
         .unwrap_or_else(|e| panic!("failed to init observer: {}", e));
+    let runtime = config.watch_runtime();
+    telemetry.apply_runtime_changes(runtime.clone());
+
+    let address_validator = address_validator::Endpoints::new(&config.address_validator.urls);
+    let address_validator_client = address_validator::http_client(&config.address_validator.tls)?;
+
+    let span_policy = config.otel.span_policy.clone();
 
-    let address_validator_url = format!(
-        "http://{}:{}",
-        &config.address_validator_host, &config.address_validator_port
+    let datastore = datastore::InMemory::new();
+    let (health_state, health_service) =
+        service::health::health_service(service::health::Checker::new(
+            datastore.clone(),
+            address_validator_client.clone(),
+            address_validator.clone(),
+            &config.otel.endpoint,
+        ));
+
+    let publisher =
+        event::FilePublisher::new(&config.event_broker_dir, event::TENANT_EVENTS_TOPIC)?;
+    let (relay_stop_tx, relay_stop_rx) = tokio::sync::oneshot::channel();
+    let relay = tokio::spawn(
+        event::outbox::OutboxRelay::new(datastore.clone(), publisher).run(relay_stop_rx),
     );
 
-    let addr = format!("0.0.0.0:{}", &config.port).parse()?;
-    tracing::info!("TenentService listening on: {}", &addr);
-    tonic::transport::Server::builder()
-        .layer(observe::middleware::trace_layer())
+    let addr: std::net::SocketAddr = format!("0.0.0.0:{}", &config.port).parse()?;
+    let tls = config.tls.map(tls::Acceptor::new).transpose()?;
+    tracing::info!(
+        "TenentService listening on: {} ({})",
+        &addr,
+        if tls.is_some() { "tls" } else { "plaintext" }
+    );
+    let (signal_tx, signal_rx) = tokio::sync::oneshot::channel();
+    let (closing_tx, closing_rx) = tokio::sync::watch::channel(false);
+    let router = tonic::transport::Server::builder()
+        .layer(common::observe::grpc_server::trace_layer(span_policy))
         .layer(tower_http::catch_panic::CatchPanicLayer::new())
+        .add_service(health_service)
         .add_service(service::reflection::reflection_service()?)
         .add_service(service::tenant::tenant_service(
-            datastore::InMemory::new(),
-            client::Client::new(),
-            address_validator_url,
-        ))
-        .serve_with_shutdown(addr, shutdown_signal())
-        .await?;
-
-    shutdown_tracer();
+            datastore,
+            client::Client::new(address_validator_client, runtime),
+            address_validator,
+            closing_rx,
+        ));
+    let shutdown = async move {
+        shutdown_signal(health_state).await;
+        let _ = closing_tx.send(true);
+        let _ = signal_tx.send(());
+    };
+    // NOTE: TLS の場合はハンドシェイク済みの接続を渡すため、待ち受け方で future の型が変わる
+    let server: std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<(), tonic::transport::Error>>>,
+    > = match tls {
+        Some(acceptor) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            Box::pin(router.serve_with_incoming_shutdown(acceptor.incoming(listener), shutdown))
+        }
+        None => Box::pin(router.serve_with_shutdown(addr, shutdown)),
+    };
+    serve_with_deadline(server, signal_rx, config.drain_timeout).await?;
+
+    // NOTE: サーバーが停止して outbox への書き込みがなくなってから relay を止める
+    let _ = relay_stop_tx.send(());
+    if let Err(e) = relay.await {
+        tracing::error!("outbox relay panicked: {}", e);
+    }
+
+    telemetry.shutdown().await;
     Ok(())
 }
 
-async fn shutdown_signal() {
+// NOTE: シグナル受信後 drain_timeout を過ぎても接続が残っている場合は、drain を打ち切って終了する
+async fn serve_with_deadline<E>(
+    server: impl std::future::Future<Output = Result<(), E>>,
+    signal_received: tokio::sync::oneshot::Receiver<()>,
+    drain_timeout: std::time::Duration,
+) -> Result<(), E> {
+    let deadline = async {
+        if signal_received.await.is_err() {
+            // NOTE: シグナルを受け取らずにサーバーが終了した場合は deadline を設けない
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(drain_timeout).await;
+    };
+    tokio::select! {
+        result = server => result,
+        _ = deadline => {
+            tracing::warn!("drain timed out after {:?}, closing remaining connections", drain_timeout);
+            Ok(())
+        }
+    }
+}
+
+async fn shutdown_signal(health_state: service::health::HealthState) {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -57,4 +141,8 @@ async fn shutdown_signal() {
     }
 
     tracing::info!("signal received, starting graceful shutdown");
+    // NOTE: ロードバランサーが NOT_SERVING を検知してトラフィックを外すまで待ってから
+    // 新規接続の受け付けを止める
+    health_state.shutdown().await;
+    tokio::time::sleep(READINESS_DRAIN_DELAY).await;
 }
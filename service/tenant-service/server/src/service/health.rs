@@ -0,0 +1,252 @@
+use tonic::server::NamedService as _;
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+const DEFAULT_OTLP_PORT: u16 = 4317;
+// NOTE: 空文字はサーバー全体の status を表す
+const SERVER_SERVICE_NAME: &str = "";
+const COLLECTOR_SERVICE_NAME: &str = "opentelemetry.collector";
+
+pub fn health_service(
+    checker: Checker,
+) -> (
+    HealthState,
+    tonic_health::pb::health_server::HealthServer<impl tonic_health::pb::health_server::Health>,
+) {
+    let (reporter, service) = tonic_health::server::health_reporter();
+    let state = HealthState {
+        inner: std::sync::Arc::new(tokio::sync::Mutex::new(State {
+            reporter,
+            shutting_down: false,
+        })),
+    };
+    tokio::spawn(state.clone().run(checker));
+    (state, service)
+}
+
+#[derive(Clone)]
+pub struct HealthState {
+    inner: std::sync::Arc<tokio::sync::Mutex<State>>,
+}
+
+struct State {
+    reporter: tonic_health::server::HealthReporter,
+    shutting_down: bool,
+}
+
+impl HealthState {
+    // NOTE: 接続を drain する前に NOT_SERVING にしてロードバランサーから外してもらう
+    // 以降は定期チェックの結果で上書きしない
+    pub async fn shutdown(&self) {
+        let mut state = self.inner.lock().await;
+        state.shutting_down = true;
+        for service in [SERVER_SERVICE_NAME, tenant_service_name()] {
+            state
+                .reporter
+                .set_service_status(service, tonic_health::ServingStatus::NotServing)
+                .await;
+        }
+        tracing::info!("health status changed to NOT_SERVING");
+    }
+
+    async fn run(self, checker: Checker) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let report = checker.check().await;
+
+            let mut state = self.inner.lock().await;
+            if state.shutting_down {
+                return;
+            }
+            for (service, status) in statuses(&report) {
+                state.reporter.set_service_status(service, status).await;
+            }
+        }
+    }
+}
+
+// NOTE: telemetry を送れなくてもリクエストは処理できるため、collector の状態は serving の判定に含めない
+fn statuses(report: &Report) -> [(&'static str, tonic_health::ServingStatus); 3] {
+    let status = serving_status(report.address_validator && report.datastore);
+    [
+        (SERVER_SERVICE_NAME, status),
+        (tenant_service_name(), status),
+        (COLLECTOR_SERVICE_NAME, serving_status(report.otlp_exporter)),
+    ]
+}
+
+fn tenant_service_name() -> &'static str {
+    proto::tenant::v1::tenant_service_server::TenantServiceServer::<super::tenant::TenantService>::NAME
+}
+
+fn serving_status(ok: bool) -> tonic_health::ServingStatus {
+    if ok {
+        tonic_health::ServingStatus::Serving
+    } else {
+        tonic_health::ServingStatus::NotServing
+    }
+}
+
+pub struct Checker {
+    datastore: crate::datastore::InMemory,
+    // NOTE: チェックのたびに trace が作られないよう tracing middleware のない client を使う
+    client: reqwest::Client,
+    address_validator: crate::address_validator::Endpoints,
+    otlp_endpoint: String,
+}
+
+struct Report {
+    address_validator: bool,
+    datastore: bool,
+    otlp_exporter: bool,
+}
+
+impl Checker {
+    pub fn new(
+        datastore: crate::datastore::InMemory,
+        client: reqwest::Client,
+        address_validator: crate::address_validator::Endpoints,
+        otlp_endpoint: impl Into<String>,
+    ) -> Self {
+        Self {
+            datastore,
+            client,
+            address_validator,
+            otlp_endpoint: otlp_endpoint.into(),
+        }
+    }
+
+    async fn check(&self) -> Report {
+        let (address_validator, datastore, otlp_exporter) = tokio::join!(
+            self.check_address_validator(),
+            self.check_datastore(),
+            self.check_otlp_exporter(),
+        );
+        Report {
+            address_validator: report("address validator", address_validator),
+            datastore: report("datastore", datastore),
+            otlp_exporter: report("otlp exporter", otlp_exporter),
+        }
+    }
+
+    // NOTE: endpoint ごとの結果を振り分けに反映し、1 つでも使えれば healthy とする
+    async fn check_address_validator(&self) -> Result<(), String> {
+        let mut errors = vec![];
+        for base_url in self.address_validator.base_urls() {
+            let result = self.check_address_validator_endpoint(base_url).await;
+            self.address_validator.set_healthy(base_url, result.is_ok());
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", base_url, e));
+            }
+        }
+        if errors.len() == self.address_validator.base_urls().count() {
+            return Err(errors.join(", "));
+        }
+        for e in errors {
+            tracing::warn!("address validator endpoint is unhealthy: {}", e);
+        }
+        Ok(())
+    }
+
+    async fn check_address_validator_endpoint(
+        &self,
+        base_url: &reqwest::Url,
+    ) -> Result<(), String> {
+        let res = self
+            .client
+            .get(crate::address_validator::endpoint_url(
+                base_url,
+                &["healthz"],
+            ))
+            .timeout(CHECK_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("unexpected status: {}", res.status()));
+        }
+        Ok(())
+    }
+
+    async fn check_datastore(&self) -> Result<(), String> {
+        tokio::time::timeout(CHECK_TIMEOUT, self.datastore.ping())
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // NOTE: exporter 自体は送信に失敗しても状態を持たないため、collector に TCP で接続できるかを見る
+    async fn check_otlp_exporter(&self) -> Result<(), String> {
+        let uri: http::Uri = self.otlp_endpoint.parse().map_err(|e| format!("{}", e))?;
+        let host = uri
+            .host()
+            .ok_or_else(|| format!("missing host in {}", &self.otlp_endpoint))?;
+        let port = uri.port_u16().unwrap_or(DEFAULT_OTLP_PORT);
+        tokio::time::timeout(CHECK_TIMEOUT, tokio::net::TcpStream::connect((host, port)))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn report(name: &str, result: Result<(), String>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("health check failed: {}: {}", name, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn checker(otlp_endpoint: String) -> super::Checker {
+        super::Checker::new(
+            crate::datastore::InMemory::new(),
+            reqwest::Client::new(),
+            crate::address_validator::Endpoints::new(&["http://127.0.0.1:1/".parse().unwrap()]),
+            otlp_endpoint,
+        )
+    }
+
+    #[tokio::test]
+    async fn otlp_exporter_check_connects_to_the_collector() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(checker(format!("http://{}", addr))
+            .check_otlp_exporter()
+            .await
+            .is_ok());
+
+        drop(listener);
+        assert!(checker(format!("http://{}", addr))
+            .check_otlp_exporter()
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn collector_status_does_not_affect_serving() {
+        let statuses = super::statuses(&super::Report {
+            address_validator: true,
+            datastore: true,
+            otlp_exporter: false,
+        });
+        assert_eq!(
+            statuses,
+            [
+                ("", tonic_health::ServingStatus::Serving),
+                (
+                    "tenant.v1.TenantService",
+                    tonic_health::ServingStatus::Serving
+                ),
+                (
+                    "opentelemetry.collector",
+                    tonic_health::ServingStatus::NotServing
+                ),
+            ]
+        );
+    }
+}
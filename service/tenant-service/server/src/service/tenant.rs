@@ -1,14 +1,23 @@
+use tracing::Instrument as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
 pub mod model;
 
+// NOTE: 送信待ちのメッセージがこの数を超えたまま SLOW_CONSUMER_TIMEOUT を過ぎたら切断する
+const WATCH_BUFFER_SIZE: usize = 64;
+const SLOW_CONSUMER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 pub fn tenant_service(
     datastore: crate::datastore::InMemory,
     client: crate::client::Client,
-    address_validator_url: impl Into<String>,
+    address_validator: crate::address_validator::Endpoints,
+    closing: tokio::sync::watch::Receiver<bool>,
 ) -> proto::tenant::v1::tenant_service_server::TenantServiceServer<TenantService> {
     proto::tenant::v1::tenant_service_server::TenantServiceServer::new(TenantService::new(
         datastore,
         client,
-        address_validator_url,
+        address_validator,
+        closing,
     ))
 }
 
@@ -16,54 +25,86 @@ pub fn tenant_service(
 pub struct TenantService {
     datastore: crate::datastore::InMemory,
     client: crate::client::Client,
-    address_validator_url: String,
+    address_validator: crate::address_validator::Endpoints,
+    // NOTE: graceful shutdown の開始時に true になる
+    closing: tokio::sync::watch::Receiver<bool>,
 }
 
 impl TenantService {
     pub fn new(
         datastore: crate::datastore::InMemory,
         client: crate::client::Client,
-        address_validator_url: impl Into<String>,
+        address_validator: crate::address_validator::Endpoints,
+        closing: tokio::sync::watch::Receiver<bool>,
     ) -> Self {
         Self {
             datastore,
             client,
-            address_validator_url: address_validator_url.into(),
+            address_validator,
+            closing,
         }
     }
 }
 
 #[tonic::async_trait]
 impl proto::tenant::v1::tenant_service_server::TenantService for TenantService {
-    #[tracing::instrument]
+    type WatchTenantsStream = tokio_stream::wrappers::ReceiverStream<
+        Result<proto::tenant::v1::WatchTenantsResponse, tonic::Status>,
+    >;
+
+    #[tracing::instrument(fields(tenant.id = tracing::field::Empty))]
     async fn create_tenant(
         &self,
         req: tonic::Request<proto::tenant::v1::CreateTenantRequest>,
     ) -> Result<tonic::Response<proto::tenant::v1::CreateTenantResponse>, tonic::Status> {
         let req = req.into_inner();
 
-        let result: model::AddressValidatorResponse = self
-            .client
-            .request(
-                http::Method::GET,
-                format!("{}/address/{}", &self.address_validator_url, req.address),
-                tracing::Span::current(),
-            )
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("{}", e.to_string());
-                tonic::Status::unknown(e.to_string())
-            })?
-            .json()
-            .await
-            .map_err(|e| {
-                tracing::error!("{}", e.to_string());
-                tonic::Status::internal(e.to_string())
-            })?;
-        let tenant = model::Tenant::new(req.name, result.into());
-        let id = tenant.id;
-        self.datastore.insert_tenant(id, tenant).await;
+        // NOTE: 下流のサービスにも tenant.id を伝播させるため、先に ID を払い出す
+        let id = ulid::Ulid::new();
+        let span = tenant_span(id, tracing::info_span!("validate_address"));
+
+        let result: model::AddressValidatorResponse = async {
+            if !self.client.try_acquire() {
+                return Err(tonic::Status::resource_exhausted(
+                    "address validator rate limit exceeded",
+                ));
+            }
+            let base_url = self.address_validator.pick();
+            self.client
+                .request(
+                    http::Method::GET,
+                    crate::address_validator::endpoint_url(&base_url, &["address", &req.address]),
+                    tracing::Span::current(),
+                )
+                .send()
+                .await
+                .map_err(|e| {
+                    tracing::error!("{}", e.to_string());
+                    // NOTE: 次のヘルスチェックまで他の endpoint を使う
+                    self.address_validator.set_healthy(&base_url, false);
+                    tonic::Status::unknown(e.to_string())
+                })?
+                .json()
+                .await
+                .map_err(|e| {
+                    tracing::error!("{}", e.to_string());
+                    tonic::Status::internal(e.to_string())
+                })
+        }
+        .instrument(span)
+        .await?;
+        let quota: model::Quota = req.quota.unwrap_or_default().into();
+        let tenant = model::Tenant::new(id, req.name.clone(), result.into(), quota);
+        let event = crate::event::FilePublisher::envelope(
+            proto::tenant::v1::tenant_event::Event::Created(proto::tenant::v1::TenantCreated {
+                tenant_id: Some(proto::lib::v1::Ulid {
+                    value: id.to_string(),
+                }),
+                name: req.name,
+                quota: Some(quota.into()),
+            }),
+        );
+        self.datastore.insert_tenant(id, tenant, event).await;
         let res = proto::tenant::v1::CreateTenantResponse {
             id: Some(proto::lib::v1::Ulid {
                 value: id.to_string(),
@@ -72,6 +113,25 @@ impl proto::tenant::v1::tenant_service_server::TenantService for TenantService {
         Ok(tonic::Response::new(res))
     }
 
+    #[tracing::instrument(fields(tenant.id = tracing::field::Empty))]
+    async fn get_tenant(
+        &self,
+        req: tonic::Request<proto::tenant::v1::GetTenantRequest>,
+    ) -> Result<tonic::Response<proto::tenant::v1::GetTenantResponse>, tonic::Status> {
+        let id = parse_ulid("id", req.into_inner().id)?;
+        let span = tenant_span(id, tracing::info_span!("get_tenant.lookup"));
+        let tenant = self
+            .datastore
+            .get_tenant(id)
+            .instrument(span)
+            .await
+            .ok_or_else(|| not_found(id))?;
+        let res = proto::tenant::v1::GetTenantResponse {
+            tenant: Some(tenant.into()),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
     #[tracing::instrument]
     async fn list_tenants(
         &self,
@@ -86,4 +146,223 @@ impl proto::tenant::v1::tenant_service_server::TenantService for TenantService {
         };
         Ok(tonic::Response::new(res))
     }
+
+    #[tracing::instrument(fields(tenant.id = tracing::field::Empty))]
+    async fn delete_tenant(
+        &self,
+        req: tonic::Request<proto::tenant::v1::DeleteTenantRequest>,
+    ) -> Result<tonic::Response<proto::tenant::v1::DeleteTenantResponse>, tonic::Status> {
+        let id = parse_ulid("id", req.into_inner().id)?;
+        // NOTE: イベントの trace context にも tenant.id の baggage を載せ、item-service に伝播させる
+        let span = tenant_span(id, tracing::info_span!("delete_tenant.apply"));
+        async {
+            let event = crate::event::FilePublisher::envelope(
+                proto::tenant::v1::tenant_event::Event::Deleted(proto::tenant::v1::TenantDeleted {
+                    tenant_id: Some(proto::lib::v1::Ulid {
+                        value: id.to_string(),
+                    }),
+                }),
+            );
+            self.datastore.delete_tenant(id, event).await
+        }
+        .instrument(span)
+        .await
+        .ok_or_else(|| not_found(id))?;
+        Ok(tonic::Response::new(
+            proto::tenant::v1::DeleteTenantResponse {},
+        ))
+    }
+
+    #[tracing::instrument]
+    async fn watch_tenants(
+        &self,
+        req: tonic::Request<proto::tenant::v1::WatchTenantsRequest>,
+    ) -> Result<tonic::Response<Self::WatchTenantsStream>, tonic::Status> {
+        let resume_token = req.into_inner().resume_token;
+        let after = if resume_token.is_empty() {
+            None
+        } else {
+            Some(
+                resume_token
+                    .parse()
+                    .map_err(|_| tonic::Status::invalid_argument("invalid resume_token"))?,
+            )
+        };
+        let (backlog, changes) =
+            self.datastore
+                .watch_tenants(after)
+                .await
+                .map_err(|e| match e {
+                    crate::datastore::WatchError::Expired => tonic::Status::out_of_range(
+                        "resume_token has expired, list tenants and watch again",
+                    ),
+                    crate::datastore::WatchError::InvalidRevision => {
+                        tonic::Status::invalid_argument("invalid resume_token")
+                    }
+                })?;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(WATCH_BUFFER_SIZE + 1);
+        // NOTE: span はストリームが終わるまで続くため、リクエストの trace には含めず link でたどれるようにする
+        let span = tracing::info_span!(
+            parent: None,
+            "watch_tenants.stream",
+            stream.messages_sent = tracing::field::Empty,
+            stream.end_status = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+        span.follows_from(tracing::Span::current());
+        tokio::spawn(forward_changes(backlog, changes, self.closing.clone(), tx).instrument(span));
+        Ok(tonic::Response::new(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StreamEnd {
+    ClientDisconnected,
+    SlowConsumer,
+    ServerClosing,
+    Closed,
+}
+
+impl StreamEnd {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StreamEnd::ClientDisconnected => "client_disconnected",
+            StreamEnd::SlowConsumer => "slow_consumer",
+            StreamEnd::ServerClosing => "server_closing",
+            StreamEnd::Closed => "closed",
+        }
+    }
+}
+
+async fn forward_changes(
+    backlog: Vec<crate::datastore::TenantChange>,
+    changes: tokio::sync::broadcast::Receiver<crate::datastore::TenantChange>,
+    closing: tokio::sync::watch::Receiver<bool>,
+    tx: tokio::sync::mpsc::Sender<Result<proto::tenant::v1::WatchTenantsResponse, tonic::Status>>,
+) {
+    // NOTE: 切断を通知するエラーのために 1 枠空けておく
+    let Ok(error_permit) = tx.clone().try_reserve_owned() else {
+        return;
+    };
+    let mut sent = 0u64;
+    let end = stream_changes(backlog, changes, closing, &tx, &mut sent).await;
+
+    let span = tracing::Span::current();
+    span.record("stream.messages_sent", sent);
+    span.record("stream.end_status", end.as_str());
+    match end {
+        StreamEnd::SlowConsumer => {
+            span.record("otel.status_code", "ERROR");
+            tracing::warn!("disconnecting slow consumer after {} messages", sent);
+            error_permit.send(Err(tonic::Status::resource_exhausted(
+                "consumer is too slow, watch again with the last resume_token",
+            )));
+        }
+        // NOTE: drain がストリームの終了を待ち続けないよう、サーバー側から閉じて再接続を促す
+        StreamEnd::ServerClosing => {
+            error_permit.send(Err(tonic::Status::unavailable(
+                "server is shutting down, watch again with the last resume_token",
+            )));
+        }
+        StreamEnd::ClientDisconnected | StreamEnd::Closed => {}
+    }
+}
+
+async fn stream_changes(
+    backlog: Vec<crate::datastore::TenantChange>,
+    mut changes: tokio::sync::broadcast::Receiver<crate::datastore::TenantChange>,
+    mut closing: tokio::sync::watch::Receiver<bool>,
+    tx: &tokio::sync::mpsc::Sender<Result<proto::tenant::v1::WatchTenantsResponse, tonic::Status>>,
+    sent: &mut u64,
+) -> StreamEnd {
+    for change in backlog {
+        if let Err(end) = send_change(tx, change).await {
+            return end;
+        }
+        *sent += 1;
+    }
+    loop {
+        let change = tokio::select! {
+            _ = tx.closed() => return StreamEnd::ClientDisconnected,
+            _ = closing.wait_for(|closing| *closing) => return StreamEnd::ServerClosing,
+            change = changes.recv() => change,
+        };
+        let change = match change {
+            Ok(change) => change,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                return StreamEnd::SlowConsumer
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return StreamEnd::Closed,
+        };
+        if let Err(end) = send_change(tx, change).await {
+            return end;
+        }
+        *sent += 1;
+    }
+}
+
+async fn send_change(
+    tx: &tokio::sync::mpsc::Sender<Result<proto::tenant::v1::WatchTenantsResponse, tonic::Status>>,
+    change: crate::datastore::TenantChange,
+) -> Result<(), StreamEnd> {
+    let span = tenant_span(change.tenant.id, tracing::info_span!("watch_tenants.send"));
+    let change_type = match change.kind {
+        crate::datastore::ChangeKind::Created => {
+            proto::tenant::v1::watch_tenants_response::ChangeType::Created
+        }
+        crate::datastore::ChangeKind::Deleted => {
+            proto::tenant::v1::watch_tenants_response::ChangeType::Deleted
+        }
+    };
+    let res = proto::tenant::v1::WatchTenantsResponse {
+        change_type: change_type.into(),
+        tenant: Some(change.tenant.into()),
+        resume_token: change.revision.to_string(),
+    };
+    match tokio::time::timeout(SLOW_CONSUMER_TIMEOUT, tx.send(Ok(res)))
+        .instrument(span)
+        .await
+    {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(_)) => Err(StreamEnd::ClientDisconnected),
+        Err(_) => Err(StreamEnd::SlowConsumer),
+    }
+}
+
+// NOTE: 現在の span に tenant.id を記録し、tenant.id を baggage に持つ Context を span の親にする
+// BaggageSpanProcessor により、span の内側で作られる span にも tenant.id が付く
+fn tenant_span(id: ulid::Ulid, span: tracing::Span) -> tracing::Span {
+    tracing::Span::current().record(
+        common::observe::baggage::TENANT_ID_KEY,
+        tracing::field::display(id),
+    );
+    span.set_parent(common::observe::baggage::with_tenant_id(id));
+    span
+}
+
+fn parse_ulid(
+    field: &str,
+    id: Option<proto::lib::v1::Ulid>,
+) -> Result<ulid::Ulid, InvalidArgument> {
+    let id = id.ok_or_else(|| InvalidArgument(format!("{} is required", field)))?;
+    id.value
+        .parse()
+        .map_err(|_| InvalidArgument(format!("invalid {}: {}", field, id.value)))
+}
+
+// NOTE: tonic::Status は大きいため、Status への変換は呼び出し側の ? で行う
+#[derive(Debug)]
+struct InvalidArgument(String);
+
+impl From<InvalidArgument> for tonic::Status {
+    fn from(e: InvalidArgument) -> Self {
+        tonic::Status::invalid_argument(e.0)
+    }
+}
+
+fn not_found(id: ulid::Ulid) -> tonic::Status {
+    tonic::Status::not_found(format!("tenant not found: {}", id))
 }
@@ -8,28 +8,28 @@ pub struct AddressValidatorResponse {
     addr: Option<String>,
 }
 
-impl Into<Address> for AddressValidatorResponse {
-    fn into(self) -> Address {
-        let normalized_address = match self.level {
+impl From<AddressValidatorResponse> for Address {
+    fn from(res: AddressValidatorResponse) -> Self {
+        let normalized_address = match res.level {
             1 => Some(NormalizedAddress::Prefecture {
-                prefecture: self.pref.unwrap(),
-                other: self.addr.unwrap(),
+                prefecture: res.pref.unwrap(),
+                other: res.addr.unwrap(),
             }),
             2 => Some(NormalizedAddress::City {
-                prefecture: self.pref.unwrap(),
-                city: self.city.unwrap(),
-                other: self.addr.unwrap(),
+                prefecture: res.pref.unwrap(),
+                city: res.city.unwrap(),
+                other: res.addr.unwrap(),
             }),
             3 => Some(NormalizedAddress::Town {
-                prefecture: self.pref.unwrap(),
-                city: self.city.unwrap(),
-                town: self.town.unwrap(),
-                other: self.addr.unwrap(),
+                prefecture: res.pref.unwrap(),
+                city: res.city.unwrap(),
+                town: res.town.unwrap(),
+                other: res.addr.unwrap(),
             }),
             _ => None,
         };
         Address {
-            full: self.full,
+            full: res.full,
             normalized_address,
         }
     }
@@ -60,19 +60,19 @@ pub struct Address {
     normalized_address: Option<NormalizedAddress>,
 }
 
-impl Into<proto::tenant::v1::Address> for Address {
-    fn into(self) -> proto::tenant::v1::Address {
-        if self.normalized_address.is_none() {
+impl From<Address> for proto::tenant::v1::Address {
+    fn from(address: Address) -> Self {
+        if address.normalized_address.is_none() {
             return proto::tenant::v1::Address {
                 level: proto::tenant::v1::address::NormalizationLevel::NotNomalized.into(),
-                full: self.full,
+                full: address.full,
                 normalized_address: None,
             };
         }
-        match self.normalized_address.unwrap() {
+        match address.normalized_address.unwrap() {
             NormalizedAddress::Prefecture { prefecture, other } => proto::tenant::v1::Address {
                 level: proto::tenant::v1::address::NormalizationLevel::Prefecture.into(),
-                full: self.full,
+                full: address.full,
                 normalized_address: Some(
                     proto::tenant::v1::address::NormalizedAddress::Prefecture(
                         proto::tenant::v1::address::Prefecture { prefecture, other },
@@ -85,7 +85,7 @@ impl Into<proto::tenant::v1::Address> for Address {
                 other,
             } => proto::tenant::v1::Address {
                 level: proto::tenant::v1::address::NormalizationLevel::City.into(),
-                full: self.full,
+                full: address.full,
                 normalized_address: Some(proto::tenant::v1::address::NormalizedAddress::City(
                     proto::tenant::v1::address::City {
                         prefecture,
@@ -101,7 +101,7 @@ impl Into<proto::tenant::v1::Address> for Address {
                 other,
             } => proto::tenant::v1::Address {
                 level: proto::tenant::v1::address::NormalizationLevel::Town.into(),
-                full: self.full,
+                full: address.full,
                 normalized_address: Some(proto::tenant::v1::address::NormalizedAddress::Town(
                     proto::tenant::v1::address::Town {
                         prefecture,
@@ -115,29 +115,60 @@ impl Into<proto::tenant::v1::Address> for Address {
     }
 }
 
+// NOTE: 0 の場合は無制限。制限は item-service が適用する
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quota {
+    max_items: u64,
+    requests_per_minute: u32,
+}
+
+impl From<proto::tenant::v1::Quota> for Quota {
+    fn from(quota: proto::tenant::v1::Quota) -> Self {
+        Self {
+            max_items: quota.max_items,
+            requests_per_minute: quota.requests_per_minute,
+        }
+    }
+}
+
+impl From<Quota> for proto::tenant::v1::Quota {
+    fn from(quota: Quota) -> Self {
+        proto::tenant::v1::Quota {
+            max_items: quota.max_items,
+            requests_per_minute: quota.requests_per_minute,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Tenant {
     pub id: ulid::Ulid,
     name: String,
     address: Address,
+    quota: Quota,
 }
 
 impl Tenant {
-    pub fn new(name: String, address: Address) -> Self {
-        let id = ulid::Ulid::new();
-        Self { id, name, address }
+    pub fn new(id: ulid::Ulid, name: String, address: Address, quota: Quota) -> Self {
+        Self {
+            id,
+            name,
+            address,
+            quota,
+        }
     }
 }
 
-impl Into<proto::tenant::v1::list_tenants_response::Tenant> for Tenant {
-    fn into(self) -> proto::tenant::v1::list_tenants_response::Tenant {
+impl From<Tenant> for proto::tenant::v1::list_tenants_response::Tenant {
+    fn from(tenant: Tenant) -> Self {
         let id = Some(proto::lib::v1::Ulid {
-            value: self.id.to_string(),
+            value: tenant.id.to_string(),
         });
         proto::tenant::v1::list_tenants_response::Tenant {
             id,
-            name: self.name,
-            address: Some(self.address.into()),
+            name: tenant.name,
+            address: Some(tenant.address.into()),
+            quota: Some(tenant.quota.into()),
         }
     }
 }
@@ -0,0 +1,175 @@
+use std::sync::{Arc, RwLock};
+
+const RELOAD_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+pub type TlsStream = tokio_rustls::server::TlsStream<tokio::net::TcpStream>;
+
+// NOTE: tonic の ServerTlsConfig は起動後に証明書を差し替えられないため、
+// rustls の設定を自前で持ち、接続ごとに最新の設定でハンドシェイクする
+#[derive(Clone)]
+pub struct Acceptor {
+    tls: Arc<crate::config::ServerTls>,
+    config: Arc<RwLock<Arc<tokio_rustls::rustls::ServerConfig>>>,
+}
+
+impl Acceptor {
+    pub fn new(tls: crate::config::ServerTls) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = server_config(&tls)?;
+        Ok(Self {
+            tls: Arc::new(tls),
+            config: Arc::new(RwLock::new(Arc::new(config))),
+        })
+    }
+
+    // NOTE: ハンドシェイクが終わった接続だけを tonic に渡す
+    // 遅いクライアントが accept を止めないよう、ハンドシェイクは接続ごとの task で行う
+    pub fn incoming(
+        self,
+        listener: tokio::net::TcpListener,
+    ) -> tokio_stream::wrappers::ReceiverStream<Result<TlsStream, std::io::Error>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(128);
+        tokio::spawn(self.clone().watch_files(tx.clone()));
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote) = tokio::select! {
+                    _ = tx.closed() => return,
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!("failed to accept connection: {}", e);
+                            continue;
+                        }
+                    },
+                };
+                let acceptor = tokio_rustls::TlsAcceptor::from(self.current());
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send(Ok(stream)).await;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::debug!("TLS handshake with {} failed: {}", remote, e)
+                        }
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", remote),
+                    }
+                });
+            }
+        });
+        tokio_stream::wrappers::ReceiverStream::new(rx)
+    }
+
+    fn current(&self) -> Arc<tokio_rustls::rustls::ServerConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    // NOTE: 証明書ファイルの更新時刻をポーリングし、変わったら読み直す
+    // 既存の接続はそのままで、新しい接続から新しい証明書を使う
+    async fn watch_files<T>(self, tx: tokio::sync::mpsc::Sender<T>) {
+        let mut modified = self.modified_at();
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        loop {
+            interval.tick().await;
+            if tx.is_closed() {
+                return;
+            }
+            let latest = self.modified_at();
+            if latest == modified {
+                continue;
+            }
+            modified = latest;
+            self.reload();
+        }
+    }
+
+    fn modified_at(&self) -> Vec<Option<std::time::SystemTime>> {
+        [
+            Some(&self.tls.cert),
+            Some(&self.tls.key),
+            self.tls.client_ca.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+    }
+
+    // NOTE: cert と key の書き換えが揃う前に読むと失敗するが、以前の証明書のまま次の更新を待つ
+    fn reload(&self) {
+        let span = tracing::info_span!(
+            "tls.reload",
+            tls.cert = %self.tls.cert.display(),
+            otel.status_code = tracing::field::Empty,
+            error.message = tracing::field::Empty,
+        );
+        let _enter = span.enter();
+
+        match server_config(&self.tls) {
+            Ok(config) => {
+                *self.config.write().unwrap() = Arc::new(config);
+                tracing::info!("reloaded TLS certificates");
+            }
+            Err(e) => {
+                span.record("otel.status_code", "ERROR");
+                span.record("error.message", e.to_string());
+                tracing::warn!("keeping the previous TLS certificates: {}", e);
+            }
+        }
+    }
+}
+
+fn server_config(
+    tls: &crate::config::ServerTls,
+) -> Result<tokio_rustls::rustls::ServerConfig, Box<dyn std::error::Error>> {
+    let certs = read_pem(&tls.cert)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(der) => {
+                Some(tokio_rustls::rustls::Certificate(der))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if certs.is_empty() {
+        return Err(format!("no certificate in {}", tls.cert.display()).into());
+    }
+    let key = read_pem(&tls.key)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::ECKey(der) => Some(tokio_rustls::rustls::PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("no private key in {}", tls.key.display()))?;
+
+    let builder = tokio_rustls::rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match &tls.client_ca {
+        Some(client_ca) => {
+            let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+            for item in read_pem(client_ca)? {
+                if let rustls_pemfile::Item::X509Certificate(der) = item {
+                    roots.add(&tokio_rustls::rustls::Certificate(der))?;
+                }
+            }
+            if roots.is_empty() {
+                return Err(format!("no certificate in {}", client_ca.display()).into());
+            }
+            builder.with_client_cert_verifier(
+                tokio_rustls::rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed(),
+            )
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(config)
+}
+
+fn read_pem(path: &std::path::Path) -> Result<Vec<rustls_pemfile::Item>, String> {
+    let pem =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    rustls_pemfile::read_all(&mut pem.as_slice())
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+}